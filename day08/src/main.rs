@@ -1,5 +1,7 @@
 use std::io::{self, BufRead};
 
+use screen::ocr;
+
 const WIDTH: usize = 25;
 const HEIGHT: usize = 6;
 
@@ -50,7 +52,7 @@ fn main() {
 
     for row in 0..HEIGHT {
         for col in 0..WIDTH {
-            if flatten_image[idx(0, row, col)] == 0 {
+            if flattened_image[idx(0, row, col)] == 0 {
                 print!("\u{2588}");
             } else {
                 print!(" ");
@@ -58,4 +60,16 @@ fn main() {
         }
         println!("");
     }
+
+    let pixels: Vec<Vec<bool>> = (0..HEIGHT)
+        .map(|row| {
+            (0..WIDTH)
+                .map(|col| flattened_image[idx(0, row, col)] == 1)
+                .collect()
+        })
+        .collect();
+    match ocr::read(&pixels) {
+        Ok(text) => println!("{}", text),
+        Err(e) => eprintln!("Unable to read image: {}", e),
+    }
 }
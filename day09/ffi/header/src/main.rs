@@ -0,0 +1,12 @@
+use std::env::args;
+
+/// Regenerates the C header for the VM's FFI. Run it with `make header`
+/// after changing `src/ffi.rs`; the header it writes is checked in.
+fn main() {
+    let crate_dir = args().nth(1).expect("No crate directory!");
+    let out = args().nth(2).expect("No output file!");
+
+    cbindgen::generate(&crate_dir)
+        .expect("Unable to generate C bindings")
+        .write_to_file(out);
+}
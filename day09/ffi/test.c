@@ -0,0 +1,92 @@
+/* Drives the Intcode VM through the C interface. Build and run with `make`. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "intcode.h"
+
+#define CHECK(cond)                                                    \
+    do {                                                               \
+        if (!(cond)) {                                                 \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,     \
+                    __LINE__, #cond);                                  \
+            return 1;                                                  \
+        }                                                              \
+    } while (0)
+
+/* Outputs a copy of itself (day 9 example). */
+static const int64_t QUINE[] = {109, 1, 204, -1, 1001, 100, 1, 100,
+                                1008, 100, 16, 101, 1006, 101, 0, 99};
+
+/* Reads a number and outputs it doubled, forever. */
+static const int64_t DOUBLER[] = {3, 9, 1002, 9, 2, 9, 4, 9, 1105, 1, 0};
+
+static int test_quine(void) {
+    size_t len = sizeof(QUINE) / sizeof(QUINE[0]);
+    IntcodeVm *vm = intcode_new(QUINE, len);
+    int64_t val;
+
+    CHECK(intcode_run(vm) == IntcodeStatus_Halted);
+    for (size_t i = 0; i < len; i++) {
+        CHECK(intcode_pop_output(vm, &val));
+        CHECK(val == QUINE[i]);
+    }
+    CHECK(!intcode_pop_output(vm, &val));
+
+    intcode_free(vm);
+    return 0;
+}
+
+static int test_io_and_snapshots(void) {
+    IntcodeVm *vm = intcode_new(DOUBLER, sizeof(DOUBLER) / sizeof(DOUBLER[0]));
+    int64_t val;
+
+    CHECK(intcode_run(vm) == IntcodeStatus_WaitingForInput);
+    intcode_push_input(vm, 21);
+    CHECK(intcode_run(vm) == IntcodeStatus_WaitingForInput);
+    CHECK(intcode_pop_output(vm, &val) && val == 42);
+
+    char *snap = intcode_snapshot(vm);
+    CHECK(snap != NULL);
+
+    intcode_push_input(vm, 5);
+    CHECK(intcode_run(vm) == IntcodeStatus_WaitingForInput);
+    CHECK(intcode_pop_output(vm, &val) && val == 10);
+
+    /* Restoring rewinds memory, so input 5 is consumed again. */
+    CHECK(intcode_restore(vm, snap));
+    intcode_free_string(snap);
+    intcode_push_input(vm, 5);
+    CHECK(intcode_run(vm) == IntcodeStatus_WaitingForInput);
+    CHECK(intcode_pop_output(vm, &val) && val == 10);
+
+    /* A bad snapshot is refused and the VM carries on as before. */
+    CHECK(!intcode_restore(vm, "page 999999999999999999 1\n"));
+    CHECK(intcode_status(vm) == IntcodeStatus_WaitingForInput);
+    intcode_push_input(vm, 7);
+    CHECK(intcode_run(vm) == IntcodeStatus_WaitingForInput);
+    CHECK(intcode_pop_output(vm, &val) && val == 14);
+
+    intcode_free(vm);
+    return 0;
+}
+
+static int test_fault(void) {
+    static const int64_t BAD[] = {42};
+    IntcodeVm *vm = intcode_new(BAD, 1);
+
+    CHECK(intcode_run(vm) == IntcodeStatus_Faulted);
+    CHECK(intcode_status(vm) == IntcodeStatus_Faulted);
+
+    intcode_free(vm);
+    return 0;
+}
+
+int main(void) {
+    if (test_quine() || test_io_and_snapshots() || test_fault()) {
+        return 1;
+    }
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,106 @@
+#ifndef INTCODE_H
+#define INTCODE_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum IntcodeStatus {
+  IntcodeStatus_Running = 0,
+  IntcodeStatus_WaitingForInput = 1,
+  IntcodeStatus_Halted = 2,
+  /**
+   * The program hit an invalid instruction, or the VM panicked; it can't
+   * continue.
+   */
+  IntcodeStatus_Faulted = 3,
+} IntcodeStatus;
+
+/**
+ * Opaque handle to a VM.
+ */
+typedef struct IntcodeVm IntcodeVm;
+
+/**
+ * Creates a VM loaded with `len` words of program. Free it with
+ * `intcode_free`.
+ *
+ * # Safety
+ *
+ * `prog` must point to `len` readable words.
+ */
+struct IntcodeVm *intcode_new(const int64_t *prog, size_t len);
+
+/**
+ * # Safety
+ *
+ * `vm` must come from `intcode_new` and not be used afterwards.
+ */
+void intcode_free(struct IntcodeVm *vm);
+
+/**
+ * # Safety
+ *
+ * `vm` must be a live handle from `intcode_new`.
+ */
+void intcode_push_input(struct IntcodeVm *vm, int64_t val);
+
+/**
+ * Runs until the program halts or needs input that hasn't been pushed.
+ *
+ * # Safety
+ *
+ * `vm` must be a live handle from `intcode_new`.
+ */
+enum IntcodeStatus intcode_run(struct IntcodeVm *vm);
+
+/**
+ * # Safety
+ *
+ * `vm` must be a live handle from `intcode_new`.
+ */
+enum IntcodeStatus intcode_status(const struct IntcodeVm *vm);
+
+/**
+ * Takes the oldest pending output into `*out`. Returns false if there is
+ * none.
+ *
+ * # Safety
+ *
+ * `vm` must be a live handle from `intcode_new` and `out` writable.
+ */
+bool intcode_pop_output(struct IntcodeVm *vm, int64_t *out);
+
+/**
+ * Serializes the VM state as a NUL-terminated string. Free it with
+ * `intcode_free_string`.
+ *
+ * # Safety
+ *
+ * `vm` must be a live handle from `intcode_new`.
+ */
+char *intcode_snapshot(const struct IntcodeVm *vm);
+
+/**
+ * Restores state written by `intcode_snapshot`. Returns false, leaving the
+ * VM as it was, if the snapshot can't be parsed.
+ *
+ * # Safety
+ *
+ * `vm` must be a live handle from `intcode_new` and `snapshot` a
+ * NUL-terminated string.
+ */
+bool intcode_restore(struct IntcodeVm *vm, const char *snapshot);
+
+/**
+ * # Safety
+ *
+ * `s` must come from `intcode_snapshot` and not be used afterwards.
+ */
+void intcode_free_string(char *s);
+
+#endif /* INTCODE_H */
@@ -0,0 +1,99 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::{add_input, run, Context};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs a fork of `base` on one set of inputs and returns everything it
+/// output.
+pub fn probe(base: &Context, inputs: &[i64]) -> Vec<i64> {
+    let mut ctx = base.fork();
+    for &val in inputs {
+        add_input(&mut ctx, val);
+    }
+    run(&mut ctx);
+
+    Vec::from(ctx.output)
+}
+
+/// Worker threads for running many short queries against the same program,
+/// such as probing every cell of a grid. The workers start once and take
+/// batches over a channel until the pool is dropped.
+pub struct Pool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    /// Starts `size` workers; at least one.
+    pub fn new(size: usize) -> Pool {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+
+        let workers = (0..size.max(1))
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || loop {
+                    let job = queue.lock().unwrap().recv();
+                    match job {
+                        // A panicking job only loses its own results.
+                        Ok(job) => drop(panic::catch_unwind(AssertUnwindSafe(job))),
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+
+        Pool {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    /// Runs `probe` on each set of inputs, split evenly across the workers.
+    /// Returns the outputs in the same order as `inputs`.
+    pub fn run_batch(&self, base: &Context, inputs: &[Vec<i64>]) -> Vec<Vec<i64>> {
+        let chunk_size = inputs.len().div_ceil(self.workers.len()).max(1);
+        let (done, results) = mpsc::channel();
+
+        let mut chunks = 0;
+        for (i, chunk) in inputs.chunks(chunk_size).enumerate() {
+            let ctx = base.fork();
+            let chunk = chunk.to_vec();
+            let done = done.clone();
+            let job = Box::new(move || {
+                let outputs: Vec<Vec<i64>> = chunk.iter().map(|vals| probe(&ctx, vals)).collect();
+                // Only fails if the batch was already abandoned.
+                let _ = done.send((i, outputs));
+            });
+            self.jobs
+                .as_ref()
+                .unwrap()
+                .send(job)
+                .expect("Batch workers are gone");
+            chunks += 1;
+        }
+        drop(done);
+
+        let mut res: Vec<(usize, Vec<Vec<i64>>)> = results.iter().collect();
+        if res.len() != chunks {
+            panic!("A batch job panicked");
+        }
+        res.sort_by_key(|&(i, _)| i);
+
+        res.into_iter().flat_map(|(_, outputs)| outputs).collect()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // Closing the channel lets each worker finish its job and exit.
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
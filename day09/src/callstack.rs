@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use crate::symbols::Symbols;
+use crate::Context;
+
+/// A code address, followed by its symbolic location if there is one.
+fn code_addr(symbols: &Symbols, addr: usize) -> String {
+    match symbols.locate(addr) {
+        Some(loc) => format!("{} ({})", addr, loc),
+        None => addr.to_string(),
+    }
+}
+
+/// Compiled Intcode calls a function by storing the return address at
+/// `[rb+0]` and arguments above it, then jumping to the callee, which
+/// starts by growing the frame with `arb N`. It returns with `arb -N`
+/// followed by a jump through `[rb+0]`.
+#[derive(Clone)]
+pub struct Frame {
+    pub entry: usize,
+    pub base: i64,
+    pub ret: i64,
+}
+
+#[derive(Clone, Default)]
+pub struct CallStack {
+    pub frames: Vec<Frame>,
+    jumped: bool,
+}
+
+impl CallStack {
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn describe(&self, pc: usize, symbols: &Symbols) -> String {
+        let mut res = format!("call stack at pc {}:\n", code_addr(symbols, pc));
+
+        if self.frames.is_empty() {
+            res += "  (top level)\n";
+        }
+        for (i, frame) in self.frames.iter().rev().enumerate() {
+            let entry = match symbols.func(frame.entry) {
+                Some(name) => name.to_string(),
+                None => frame.entry.to_string(),
+            };
+            let ret = if frame.ret >= 0 {
+                code_addr(symbols, frame.ret as usize)
+            } else {
+                frame.ret.to_string()
+            };
+            writeln!(
+                res,
+                "  #{} function {} frame {} returns to {}",
+                i, entry, frame.base, ret
+            )
+            .unwrap();
+        }
+
+        res
+    }
+}
+
+/// Updates the call stack after the instruction at `pc` ran with relative
+/// base `rel_base`.
+pub fn track(ctx: &mut Context, opcode: usize, pc: usize, rel_base: i64) {
+    match opcode {
+        5 | 6 => ctx.calls.jumped = ctx.pc != pc + 3,
+        9 => {
+            if ctx.calls.jumped && ctx.rel_base > rel_base {
+                let ret = ctx.mem.peek(rel_base as usize);
+                ctx.calls.frames.push(Frame {
+                    entry: pc,
+                    base: ctx.rel_base,
+                    ret,
+                });
+            }
+            while ctx.calls.frames.last().is_some_and(|f| f.base > ctx.rel_base) {
+                ctx.calls.frames.pop();
+            }
+            ctx.calls.jumped = false;
+        }
+        _ => ctx.calls.jumped = false,
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use crate::{add_input, fault_report, snapshot, step, Context, Status};
+
+const PROMPT: &str = "> ";
+
+const HELP: &str = "\
+!save FILE  write a snapshot of the VM to FILE
+!load FILE  restore the VM from a snapshot in FILE
+!quit       leave the console
+";
+
+struct Console {
+    editor: Editor<()>,
+    transcript: Option<File>,
+}
+
+impl Console {
+    /// Shows text on the terminal and copies it to the transcript.
+    fn show(&mut self, text: &str) {
+        print!("{}", text);
+        io::stdout().flush().unwrap();
+        if let Some(file) = &mut self.transcript {
+            file.write_all(text.as_bytes()).expect("Unable to write transcript");
+        }
+    }
+
+    fn log(&mut self, text: &str) {
+        if let Some(file) = &mut self.transcript {
+            file.write_all(text.as_bytes()).expect("Unable to write transcript");
+        }
+    }
+
+    fn flush_output(&mut self, ctx: &mut Context) {
+        while let Some(val) = ctx.output.pop_front() {
+            if (0..128).contains(&val) {
+                self.show(&(val as u8 as char).to_string());
+            } else {
+                self.show(&format!("{}\n", val));
+            }
+        }
+    }
+
+    /// Handles a `!` command, returning false when the console should exit.
+    fn meta(&mut self, ctx: &mut Context, line: &str) -> bool {
+        let mut words = line[1..].split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some("quit"), _) => return false,
+            (Some("save"), Some(path)) => match fs::write(path, snapshot::save(ctx)) {
+                Ok(()) => self.show(&format!("[saved {}]\n", path)),
+                Err(e) => self.show(&format!("[unable to save {}: {}]\n", path, e)),
+            },
+            (Some("load"), Some(path)) => match fs::read_to_string(path) {
+                Ok(contents) => match snapshot::load(ctx, &contents) {
+                    Ok(()) => self.show(&format!("[loaded {}]\n", path)),
+                    Err(e) => self.show(&format!("[unable to load {}: {}]\n", path, e)),
+                },
+                Err(e) => self.show(&format!("[unable to load {}: {}]\n", path, e)),
+            },
+            _ => self.show(HELP),
+        }
+
+        true
+    }
+}
+
+/// Runs a text-based program interactively: output is shown as ASCII as
+/// soon as it is produced and each line typed is sent followed by a newline.
+pub fn run_console(ctx: &mut Context, transcript: Option<File>) {
+    let mut console = Console {
+        editor: Editor::new(),
+        transcript,
+    };
+
+    loop {
+        while ctx.status == Status::Running {
+            step(ctx);
+            console.flush_output(ctx);
+        }
+        if let Some(report) = fault_report(ctx) {
+            console.show(&format!("[fault: {}]\n", report.trim_end()));
+        }
+        if ctx.status != Status::WaitingForInput {
+            break;
+        }
+
+        let line = match console.editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(e) => panic!("Unable to read input: {}", e),
+        };
+        console.log(&format!("{}{}\n", PROMPT, line));
+        console.editor.add_history_entry(line.as_str());
+
+        if line.starts_with('!') {
+            if !console.meta(ctx, &line) {
+                break;
+            }
+            continue;
+        }
+
+        for b in line.bytes() {
+            add_input(ctx, i64::from(b));
+        }
+        add_input(ctx, i64::from(b'\n'));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::disasm::disasm;
+use crate::symbols::Symbols;
+use crate::Registry;
+
+/// Records which instructions ran and which way each jump and comparison
+/// went during a run.
+pub struct Coverage {
+    prog: Vec<i64>,
+    hits: HashMap<usize, usize>,
+    branches: HashMap<usize, (usize, usize)>,
+}
+
+impl Coverage {
+    pub fn new(prog: &[i64]) -> Coverage {
+        Coverage {
+            prog: prog.to_vec(),
+            hits: HashMap::new(),
+            branches: HashMap::new(),
+        }
+    }
+
+    pub fn visit(&mut self, pc: usize) {
+        *self.hits.entry(pc).or_insert(0) += 1;
+    }
+
+    pub fn branch(&mut self, pc: usize, taken: bool) {
+        let counts = self.branches.entry(pc).or_insert((0, 0));
+        if taken {
+            counts.0 += 1;
+        } else {
+            counts.1 += 1;
+        }
+    }
+
+    /// Annotated disassembly of the original program image, followed by
+    /// instruction and branch coverage totals. Functions get a label line
+    /// and commented addresses their comment.
+    pub fn report(&self, registry: &Registry, symbols: &Symbols) -> String {
+        let mut res = String::new();
+        let mut num_instrs = 0;
+        let mut num_hit = 0;
+        let mut num_dirs = 0;
+        let mut num_dirs_hit = 0;
+
+        let mut addr = 0;
+        while addr < self.prog.len() {
+            let hits = self.hits.get(&addr).cloned().unwrap_or(0);
+            if let Some(name) = symbols.func(addr) {
+                writeln!(res, "{:>8}  {:>5}  {}:", "", "", name).unwrap();
+            }
+            let comment = match symbols.comment(addr) {
+                Some(text) => format!("  ; {}", text),
+                None => String::new(),
+            };
+
+            // Fall back to data if the decoded instruction would swallow
+            // the start of one that actually ran.
+            let decoded = disasm(registry, symbols, &self.prog, addr).filter(|&(_, len)| {
+                (addr + 1..addr + len).all(|a| !self.hits.contains_key(&a))
+            });
+
+            let (text, len) = match decoded {
+                Some(decoded) => decoded,
+                None => {
+                    let label = match symbols.data(addr) {
+                        Some(name) => format!("  ; {}", name),
+                        None => String::new(),
+                    };
+                    writeln!(
+                        res,
+                        "{:>8}  {:>5}  .word {}{}{}",
+                        "", addr, self.prog[addr], label, comment
+                    )
+                    .unwrap();
+                    addr += 1;
+                    continue;
+                }
+            };
+
+            num_instrs += 1;
+            let marker = if hits > 0 {
+                num_hit += 1;
+                hits.to_string()
+            } else {
+                String::from("-")
+            };
+
+            let opcode = self.prog[addr] % 100;
+            let branch = if (5..=8).contains(&opcode) {
+                let (taken, not_taken) = self.branches.get(&addr).cloned().unwrap_or((0, 0));
+                num_dirs += 2;
+                num_dirs_hit += (taken > 0) as usize + (not_taken > 0) as usize;
+                format!("  ; T:{} F:{}", taken, not_taken)
+            } else {
+                String::new()
+            };
+
+            writeln!(
+                res,
+                "{:>8}  {:>5}  {}{}{}",
+                marker, addr, text, branch, comment
+            )
+            .unwrap();
+            addr += len;
+        }
+
+        writeln!(res).unwrap();
+        writeln!(
+            res,
+            "instructions: {}/{} ({:.1}%)",
+            num_hit,
+            num_instrs,
+            percent(num_hit, num_instrs)
+        )
+        .unwrap();
+        writeln!(
+            res,
+            "branches: {}/{} ({:.1}%)",
+            num_dirs_hit,
+            num_dirs,
+            percent(num_dirs_hit, num_dirs)
+        )
+        .unwrap();
+
+        res
+    }
+}
+
+fn percent(num: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        100.0 * num as f64 / total as f64
+    }
+}
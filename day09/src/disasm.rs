@@ -0,0 +1,61 @@
+use crate::symbols::Symbols;
+use crate::{Param, Registry};
+
+fn format_operand(symbols: &Symbols, mode: usize, val: i64, target: bool) -> String {
+    let name = match (mode, val >= 0) {
+        (0, true) => symbols.data(val as usize),
+        (1, true) if target => symbols.locate(val as usize),
+        _ => None,
+    };
+
+    match mode {
+        0 => format!("[{}]", name.unwrap_or_else(|| val.to_string())),
+        1 => name.unwrap_or_else(|| val.to_string()),
+        _ => {
+            if val < 0 {
+                format!("[rb-{}]", -val)
+            } else {
+                format!("[rb+{}]", val)
+            }
+        }
+    }
+}
+
+/// Decodes the instruction at `addr` of a program image, returning its text
+/// and length. Words that don't form a valid instruction yield `None`.
+/// Addresses are shown by name where `symbols` has one.
+pub fn disasm(
+    registry: &Registry,
+    symbols: &Symbols,
+    prog: &[i64],
+    addr: usize,
+) -> Option<(String, usize)> {
+    let opcode = prog[addr];
+    if opcode < 0 {
+        return None;
+    }
+    let opcode = opcode as usize;
+
+    let instr = registry.instrs.get(&(opcode % 100))?;
+    let len = 1 + instr.params.len();
+    if addr + len > prog.len() || opcode / 10usize.pow(1 + len as u32) != 0 {
+        return None;
+    }
+
+    let mut operands = Vec::new();
+    for (i, param) in instr.params.iter().enumerate() {
+        let mode = opcode / 10usize.pow(2 + i as u32) % 10;
+        if mode > 2 || *param == Param::Out && mode == 1 {
+            return None;
+        }
+        // The second operand of a jump is where it goes.
+        let target = (5..=6).contains(&(opcode % 100)) && i == 1;
+        operands.push(format_operand(symbols, mode, prog[addr + 1 + i], target));
+    }
+
+    if operands.is_empty() {
+        Some((instr.name.to_string(), len))
+    } else {
+        Some((format!("{} {}", instr.name, operands.join(", ")), len))
+    }
+}
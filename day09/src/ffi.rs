@@ -0,0 +1,168 @@
+//! C interface to the VM. The header in `include/intcode.h` is generated
+//! from this file by `make header` in `ffi/`.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::{add_input, run, snapshot, Context, Status};
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum IntcodeStatus {
+    Running = 0,
+    WaitingForInput = 1,
+    Halted = 2,
+    /// The program hit an invalid instruction, or the VM panicked; it can't
+    /// continue.
+    Faulted = 3,
+}
+
+/// Opaque handle to a VM.
+pub struct IntcodeVm {
+    ctx: Context,
+    faulted: bool,
+}
+
+/// Creates a VM loaded with `len` words of program. Free it with
+/// `intcode_free`.
+///
+/// # Safety
+///
+/// `prog` must point to `len` readable words.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_new(prog: *const i64, len: usize) -> *mut IntcodeVm {
+    let prog = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(prog, len)
+    };
+
+    Box::into_raw(Box::new(IntcodeVm {
+        ctx: Context::new(prog),
+        faulted: false,
+    }))
+}
+
+/// # Safety
+///
+/// `vm` must come from `intcode_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_free(vm: *mut IntcodeVm) {
+    if !vm.is_null() {
+        drop(Box::from_raw(vm));
+    }
+}
+
+/// # Safety
+///
+/// `vm` must be a live handle from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_push_input(vm: *mut IntcodeVm, val: i64) {
+    add_input(&mut (*vm).ctx, val);
+}
+
+/// Runs until the program halts or needs input that hasn't been pushed.
+///
+/// # Safety
+///
+/// `vm` must be a live handle from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_run(vm: *mut IntcodeVm) -> IntcodeStatus {
+    let vm = &mut *vm;
+
+    if !vm.faulted {
+        let ctx = &mut vm.ctx;
+        vm.faulted = panic::catch_unwind(AssertUnwindSafe(|| run(ctx))).is_err();
+    }
+
+    intcode_status(vm)
+}
+
+/// # Safety
+///
+/// `vm` must be a live handle from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_status(vm: *const IntcodeVm) -> IntcodeStatus {
+    let vm = &*vm;
+
+    if vm.faulted {
+        return IntcodeStatus::Faulted;
+    }
+    match vm.ctx.status {
+        Status::Running => IntcodeStatus::Running,
+        Status::WaitingForInput => IntcodeStatus::WaitingForInput,
+        Status::Halted => IntcodeStatus::Halted,
+        Status::Faulted(_) => IntcodeStatus::Faulted,
+    }
+}
+
+/// Takes the oldest pending output into `*out`. Returns false if there is
+/// none.
+///
+/// # Safety
+///
+/// `vm` must be a live handle from `intcode_new` and `out` writable.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pop_output(vm: *mut IntcodeVm, out: *mut i64) -> bool {
+    match (*vm).ctx.output.pop_front() {
+        Some(val) => {
+            *out = val;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Serializes the VM state as a NUL-terminated string. Free it with
+/// `intcode_free_string`.
+///
+/// # Safety
+///
+/// `vm` must be a live handle from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_snapshot(vm: *const IntcodeVm) -> *mut c_char {
+    match CString::new(snapshot::save(&(*vm).ctx)) {
+        Ok(s) => s.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Restores state written by `intcode_snapshot`. Returns false, leaving the
+/// VM as it was, if the snapshot can't be parsed. If restoring panics
+/// partway the VM is left faulted.
+///
+/// # Safety
+///
+/// `vm` must be a live handle from `intcode_new` and `snapshot` a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_restore(vm: *mut IntcodeVm, snapshot: *const c_char) -> bool {
+    let vm = &mut *vm;
+    let contents = CStr::from_ptr(snapshot).to_string_lossy();
+
+    let ctx = &mut vm.ctx;
+    match panic::catch_unwind(AssertUnwindSafe(|| snapshot::load(ctx, &contents))) {
+        Ok(Ok(())) => {
+            vm.faulted = false;
+            true
+        }
+        Ok(Err(_)) => false,
+        Err(_) => {
+            vm.faulted = true;
+            false
+        }
+    }
+}
+
+/// # Safety
+///
+/// `s` must come from `intcode_snapshot` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
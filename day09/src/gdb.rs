@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::{add_input, fault_report, step, Context, Status};
+
+/// Intcode words are exposed to gdb as 8-byte little-endian values, so byte
+/// address `a` is byte `a % 8` of word `a / 8`. The pc register is a byte
+/// address to match; rel_base is reported in words as the VM sees it.
+const WORD: usize = 8;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.intcode.core">
+    <reg name="pc" bitsize="64" type="code_ptr" regnum="0"/>
+    <reg name="rel_base" bitsize="64" type="int64" regnum="1"/>
+  </feature>
+</target>
+"#;
+
+/// Instructions to run between checks for a ^C from the client.
+const POLL_INTERVAL: usize = 4096;
+
+struct Conn {
+    stream: TcpStream,
+}
+
+impl Conn {
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.stream.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// Reads the next packet, acknowledging it. A bare ^C is returned as
+    /// "\x03"; acks from the client are skipped.
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            match self.read_byte()? {
+                b'$' => (),
+                0x03 => return Ok(String::from("\x03")),
+                _ => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    b => data.push(b),
+                }
+            }
+            let check = [self.read_byte()?, self.read_byte()?];
+            let check = u8::from_str_radix(&String::from_utf8_lossy(&check), 16).ok();
+
+            if check == Some(checksum(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())
+    }
+
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut buf = [0; 1];
+
+        self.stream.set_nonblocking(true)?;
+        let res = match self.stream.peek(&mut buf) {
+            Ok(1) if buf[0] == 0x03 => {
+                self.read_byte()?;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        };
+        self.stream.set_nonblocking(false)?;
+
+        res
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap_or(0))
+        .collect()
+}
+
+fn reg_from_hex(hex: &str) -> i64 {
+    let mut bytes = [0; WORD];
+    for (i, b) in from_hex(hex).into_iter().take(WORD).enumerate() {
+        bytes[i] = b;
+    }
+    i64::from_le_bytes(bytes)
+}
+
+fn parse_hex(hex: &str) -> usize {
+    usize::from_str_radix(hex, 16).unwrap_or(0)
+}
+
+/// Splits "addr,len" into its two numbers.
+fn parse_range(args: &str) -> (usize, usize) {
+    let mut parts = args.split(',');
+    let addr = parse_hex(parts.next().unwrap_or(""));
+    let len = parse_hex(parts.next().unwrap_or(""));
+    (addr, len)
+}
+
+fn read_reg(ctx: &Context, num: usize) -> Option<i64> {
+    match num {
+        0 => Some((ctx.pc * WORD) as i64),
+        1 => Some(ctx.rel_base),
+        _ => None,
+    }
+}
+
+fn write_reg(ctx: &mut Context, num: usize, val: i64) {
+    match num {
+        0 => ctx.pc = val as usize / WORD,
+        1 => ctx.rel_base = val,
+        _ => (),
+    }
+}
+
+fn read_mem(ctx: &Context, addr: usize, len: usize) -> Vec<u8> {
+    (addr..addr + len)
+        .map(|a| ctx.mem.peek(a / WORD).to_le_bytes()[a % WORD])
+        .collect()
+}
+
+fn write_mem(ctx: &mut Context, addr: usize, data: &[u8]) {
+    for (i, b) in data.iter().enumerate() {
+        let a = addr + i;
+        let mut bytes = ctx.mem.peek(a / WORD).to_le_bytes();
+        bytes[a % WORD] = *b;
+        ctx.mem[a / WORD] = i64::from_le_bytes(bytes);
+    }
+}
+
+fn read_features(args: &str) -> String {
+    // annex:offset,length
+    let range = args.trim_start_matches("target.xml:");
+    if range.len() == args.len() {
+        return String::from("E00");
+    }
+    let (off, len) = parse_range(range);
+
+    let xml = TARGET_XML.as_bytes();
+    if off >= xml.len() {
+        return String::from("l");
+    }
+    let end = (off + len).min(xml.len());
+    let chunk = String::from_utf8_lossy(&xml[off..end]);
+    if end == xml.len() {
+        format!("l{}", chunk)
+    } else {
+        format!("m{}", chunk)
+    }
+}
+
+struct Server<'a> {
+    conn: Conn,
+    breakpoints: HashSet<usize>,
+    next_input: &'a mut dyn FnMut() -> i64,
+    reported: usize,
+}
+
+impl<'a> Server<'a> {
+    /// Forwards any new program output to the gdb console.
+    fn report_output(&mut self, ctx: &Context) -> io::Result<()> {
+        while self.reported < ctx.output.len() {
+            let line = format!("output: {}\n", ctx.output[self.reported]);
+            self.conn.send(&format!("O{}", to_hex(line.as_bytes())))?;
+            self.reported += 1;
+        }
+        Ok(())
+    }
+
+    /// Runs until a breakpoint, a ^C, the end of the program or, when
+    /// `single` is set, one instruction. Returns the stop reply.
+    fn resume(&mut self, ctx: &mut Context, single: bool) -> io::Result<String> {
+        let mut count = 0;
+
+        loop {
+            if ctx.status == Status::Halted {
+                return Ok(String::from("W00"));
+            }
+            if let Some(report) = fault_report(ctx) {
+                let text = format!("fault: {}", report);
+                self.conn.send(&format!("O{}", to_hex(text.as_bytes())))?;
+                return Ok(String::from("S04"));
+            }
+            if ctx.status == Status::WaitingForInput {
+                add_input(ctx, (self.next_input)());
+            }
+
+            step(ctx);
+            count += 1;
+            self.report_output(ctx)?;
+
+            if let Status::Faulted(_) = ctx.status {
+                continue;
+            }
+            if single {
+                return Ok(String::from("S05"));
+            }
+            if ctx.status == Status::Running && self.breakpoints.contains(&ctx.pc) {
+                return Ok(String::from("T05swbreak:;"));
+            }
+            if count % POLL_INTERVAL == 0 && self.conn.interrupted()? {
+                return Ok(String::from("S02"));
+            }
+        }
+    }
+
+    fn serve(&mut self, ctx: &mut Context) -> io::Result<()> {
+        loop {
+            let packet = self.conn.read_packet()?;
+            let (cmd, args) = packet.split_at(packet.len().min(1));
+
+            let reply = match cmd {
+                "\x03" => continue,
+                "?" => String::from("S05"),
+                "g" => {
+                    let regs: Vec<u8> = (0..2)
+                        .flat_map(|n| read_reg(ctx, n).unwrap().to_le_bytes().to_vec())
+                        .collect();
+                    to_hex(&regs)
+                }
+                "G" => {
+                    for n in 0..2 {
+                        if let Some(hex) = args.get(2 * WORD * n..2 * WORD * (n + 1)) {
+                            write_reg(ctx, n, reg_from_hex(hex));
+                        }
+                    }
+                    String::from("OK")
+                }
+                "p" => match read_reg(ctx, parse_hex(args)) {
+                    Some(val) => to_hex(&val.to_le_bytes()),
+                    None => String::from("E00"),
+                },
+                "P" => {
+                    let mut parts = args.split('=');
+                    let num = parse_hex(parts.next().unwrap_or(""));
+                    write_reg(ctx, num, reg_from_hex(parts.next().unwrap_or("")));
+                    String::from("OK")
+                }
+                "m" => {
+                    let (addr, len) = parse_range(args);
+                    to_hex(&read_mem(ctx, addr, len))
+                }
+                "M" => {
+                    let mut parts = args.split(':');
+                    let (addr, _) = parse_range(parts.next().unwrap_or(""));
+                    write_mem(ctx, addr, &from_hex(parts.next().unwrap_or("")));
+                    String::from("OK")
+                }
+                "Z" | "z" => {
+                    let mut parts = args.split(',');
+                    let kind = parts.next().unwrap_or("");
+                    let addr = parse_hex(parts.next().unwrap_or("")) / WORD;
+                    match kind {
+                        "0" | "1" if cmd == "Z" => {
+                            self.breakpoints.insert(addr);
+                            String::from("OK")
+                        }
+                        "0" | "1" => {
+                            self.breakpoints.remove(&addr);
+                            String::from("OK")
+                        }
+                        _ => String::new(),
+                    }
+                }
+                "c" => self.resume(ctx, false)?,
+                "s" => self.resume(ctx, true)?,
+                "v" if args == "Cont?" => String::from("vCont;c;C;s;S"),
+                "v" if args.starts_with("Cont;") => {
+                    let single = args[5..].starts_with('s') || args[5..].starts_with('S');
+                    self.resume(ctx, single)?
+                }
+                "q" if args.starts_with("Supported") => {
+                    String::from("PacketSize=4000;qXfer:features:read+;swbreak+")
+                }
+                "q" if args.starts_with("Xfer:features:read:") => {
+                    read_features(&args["Xfer:features:read:".len()..])
+                }
+                "q" if args == "Attached" => String::from("1"),
+                "q" if args == "C" => String::from("QC1"),
+                "q" if args == "fThreadInfo" => String::from("m1"),
+                "q" if args == "sThreadInfo" => String::from("l"),
+                "H" | "T" => String::from("OK"),
+                "D" => {
+                    self.conn.send("OK")?;
+                    return Ok(());
+                }
+                "k" => {
+                    ctx.status = Status::Halted;
+                    return Ok(());
+                }
+                _ => String::new(),
+            };
+
+            self.conn.send(&reply)?;
+        }
+    }
+}
+
+/// Waits for a gdb client on the given local port and serves it until the
+/// client detaches or kills the program. Inputs the program asks for while
+/// running under gdb come from `next_input`.
+pub fn serve(ctx: &mut Context, port: u16, next_input: &mut dyn FnMut() -> i64) {
+    let listener = TcpListener::bind(("127.0.0.1", port)).expect("Unable to bind gdb port");
+    eprintln!("Waiting for gdb on 127.0.0.1:{}", port);
+
+    let (stream, addr) = listener.accept().expect("Unable to accept gdb client");
+    eprintln!("gdb connected from {}", addr);
+
+    let mut server = Server {
+        conn: Conn { stream },
+        breakpoints: HashSet::new(),
+        next_input,
+        reported: ctx.output.len(),
+    };
+    match server.serve(ctx) {
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => eprintln!("gdb disconnected"),
+        Err(e) => eprintln!("gdb connection failed: {}", e),
+        Ok(()) => (),
+    }
+}
@@ -0,0 +1,761 @@
+pub mod batch;
+pub mod callstack;
+pub mod console;
+pub mod coverage;
+pub mod disasm;
+pub mod ffi;
+pub mod gdb;
+pub mod profile;
+pub mod scan;
+pub mod session;
+pub mod snapshot;
+pub mod symbols;
+pub mod topology;
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::ops::{Index, IndexMut};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use callstack::CallStack;
+use coverage::Coverage;
+use disasm::disasm;
+use profile::Profile;
+use session::{Event, Session};
+use symbols::Symbols;
+
+pub const PAGE_SIZE: usize = 32768;
+
+#[derive(Clone, PartialEq)]
+pub enum Status {
+    Running,
+    WaitingForInput,
+    Halted,
+    /// The program did something the VM can't carry out. It stays stopped
+    /// at the offending instruction.
+    Faulted(Fault),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    UnknownOpcode(i64),
+    InvalidMode(usize),
+    ImmediateOutput(&'static str),
+    OpcodeNotInProfile(Profile, usize),
+    ModeNotInProfile(Profile, usize),
+    AssertionFailed(i64, i64),
+    NegativeAddress(i64),
+    AddressLimit(usize),
+    PageLimit(usize),
+    /// Adding the offset to the relative base doesn't fit in a word.
+    RelBaseOverflow(i64, i64),
+    ArithmeticOverflow(&'static str, i64, i64),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::UnknownOpcode(op) => write!(f, "Unrecognized opcode: {}", op),
+            Fault::InvalidMode(mode) => write!(f, "Invalid mode: {}", mode),
+            Fault::ImmediateOutput(name) => write!(f, "Immediate mode output for {}", name),
+            Fault::OpcodeNotInProfile(profile, op) => {
+                write!(f, "Opcode {} is not part of the {} profile", op, profile)
+            }
+            Fault::ModeNotInProfile(profile, mode) => {
+                write!(f, "Mode {} is not part of the {} profile", mode, profile)
+            }
+            Fault::AssertionFailed(lhs, rhs) => write!(f, "Assertion failed: {} != {}", lhs, rhs),
+            Fault::NegativeAddress(addr) => write!(f, "Negative address: {}", addr),
+            Fault::AddressLimit(addr) => write!(f, "Address {} is beyond the limit", addr),
+            Fault::PageLimit(max) => write!(f, "Program needs more than {} pages", max),
+            Fault::RelBaseOverflow(base, off) => {
+                write!(f, "Relative base overflow: {} + {}", base, off)
+            }
+            Fault::ArithmeticOverflow(name, lhs, rhs) => {
+                write!(f, "Overflow in {}: {}, {}", name, lhs, rhs)
+            }
+        }
+    }
+}
+
+pub type Page = [i64; PAGE_SIZE];
+
+/// A host device backing a range of addresses in `Vmem`. Offsets are
+/// relative to the start of the mapped range.
+pub trait Device: Send {
+    fn read(&mut self, off: usize) -> i64;
+    fn write(&mut self, off: usize, val: i64);
+}
+
+struct Mapping {
+    start: usize,
+    len: usize,
+    dev: Box<dyn Device>,
+}
+
+/// Hashes addresses, page numbers and opcodes with a multiply rather than
+/// SipHash, which otherwise dominates the cost of `step`.
+#[derive(Default)]
+pub struct AddrHasher(u64);
+
+impl Hasher for AddrHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+/// A map keyed by address, page number or opcode.
+pub type AddrMap<V> = HashMap<usize, V, BuildHasherDefault<AddrHasher>>;
+
+/// Bounds on the memory a program may use, so a runaway or hostile one
+/// faults instead of exhausting the host. `None` is unbounded.
+#[derive(Clone, Default)]
+pub struct Limits {
+    pub max_addr: Option<usize>,
+    pub max_pages: Option<usize>,
+}
+
+/// Pages are shared between forks and copied on their first write.
+pub struct Vmem {
+    pub table: AddrMap<Arc<Page>>,
+    devices: Vec<Mapping>,
+    pub limits: Limits,
+    /// Pages the program has read or written data in.
+    pub touched: BTreeSet<usize>,
+}
+
+impl Index<usize> for Vmem {
+    type Output = i64;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        let page_num = idx / PAGE_SIZE;
+        let page_off = idx % PAGE_SIZE;
+
+        &self.table[&page_num][page_off]
+    }
+}
+
+impl IndexMut<usize> for Vmem {
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        let page_num = idx / PAGE_SIZE;
+        let page_off = idx % PAGE_SIZE;
+
+        let page = self
+            .table
+            .entry(page_num)
+            .or_insert_with(|| Arc::new([0; PAGE_SIZE]));
+
+        &mut Arc::make_mut(page)[page_off]
+    }
+}
+
+impl Vmem {
+    pub fn map(&mut self, start: usize, len: usize, dev: Box<dyn Device>) {
+        if let Some(m) = self
+            .devices
+            .iter()
+            .find(|m| start < m.start + m.len && m.start < start + len)
+        {
+            panic!(
+                "Mapping [{}, {}) overlaps [{}, {})",
+                start,
+                start + len,
+                m.start,
+                m.start + m.len
+            );
+        }
+
+        self.devices.push(Mapping { start, len, dev });
+    }
+
+    fn is_mapped(&self, idx: usize) -> bool {
+        self.devices
+            .iter()
+            .any(|m| m.start <= idx && idx < m.start + m.len)
+    }
+
+    /// Checks a data access against the limits before it happens. Only
+    /// writes allocate pages.
+    pub fn check(&self, idx: i64, write: bool) -> Result<(), Fault> {
+        if idx < 0 {
+            return Err(Fault::NegativeAddress(idx));
+        }
+        let idx = idx as usize;
+
+        if let Some(max) = self.limits.max_addr {
+            if idx > max {
+                return Err(Fault::AddressLimit(idx));
+            }
+        }
+        if let Some(max) = self.limits.max_pages {
+            let allocates = !self.table.contains_key(&(idx / PAGE_SIZE)) && !self.is_mapped(idx);
+            if write && allocates && self.table.len() >= max {
+                return Err(Fault::PageLimit(max));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Summary of the pages the program used.
+    pub fn page_report(&self) -> String {
+        let pages: Vec<String> = self.touched.iter().map(|p| p.to_string()).collect();
+        let mut res = format!("pages touched: {}", pages.len());
+        if !pages.is_empty() {
+            res += &format!(" ({})", pages.join(", "));
+        }
+
+        res + &format!("\npages allocated: {}\n", self.table.len())
+    }
+
+    fn device(&mut self, idx: usize) -> Option<(&mut Box<dyn Device>, usize)> {
+        self.devices
+            .iter_mut()
+            .find(|m| m.start <= idx && idx < m.start + m.len)
+            .map(|m| (&mut m.dev, idx - m.start))
+    }
+
+    /// Data read, dispatched to a device if one is mapped at `idx`.
+    pub fn load(&mut self, idx: usize) -> i64 {
+        if let Some((dev, off)) = self.device(idx) {
+            return dev.read(off);
+        }
+
+        self.touched.insert(idx / PAGE_SIZE);
+        self.peek(idx)
+    }
+
+    /// Raw read of the page table, bypassing devices.
+    pub fn peek(&self, idx: usize) -> i64 {
+        match self.table.get(&(idx / PAGE_SIZE)) {
+            Some(page) => page[idx % PAGE_SIZE],
+            None => 0,
+        }
+    }
+
+    /// Data write, dispatched to a device if one is mapped at `idx`.
+    pub fn store(&mut self, idx: usize, val: i64) {
+        if let Some((dev, off)) = self.device(idx) {
+            dev.write(off, val);
+        } else {
+            self.touched.insert(idx / PAGE_SIZE);
+            self[idx] = val;
+        }
+    }
+}
+
+/// Reads as the number of milliseconds since the clock was mapped.
+pub struct Clock {
+    pub start: Instant,
+}
+
+impl Device for Clock {
+    fn read(&mut self, _off: usize) -> i64 {
+        self.start.elapsed().as_millis() as i64
+    }
+
+    fn write(&mut self, _off: usize, _val: i64) {}
+}
+
+/// Plain memory that reports every write to stderr.
+pub struct Watch {
+    pub base: usize,
+    pub cells: Vec<i64>,
+}
+
+impl Device for Watch {
+    fn read(&mut self, off: usize) -> i64 {
+        self.cells[off]
+    }
+
+    fn write(&mut self, off: usize, val: i64) {
+        eprintln!("[{}] {} -> {}", self.base + off, self.cells[off], val);
+        self.cells[off] = val;
+    }
+}
+
+pub fn init_vmem(prog: &[i64]) -> Vmem {
+    let mut res = Vmem {
+        table: AddrMap::default(),
+        devices: Vec::new(),
+        limits: Limits::default(),
+        touched: BTreeSet::new(),
+    };
+
+    for i in 0..prog.len() {
+        res[i] = prog[i];
+    }
+
+    res
+}
+
+pub struct Context {
+    pub mem: Vmem,
+    pub pc: usize,
+    pub status: Status,
+    pub input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+    pub rel_base: i64,
+    pub registry: Registry,
+    pub coverage: Option<Coverage>,
+    pub session: Option<Session>,
+    pub calls: CallStack,
+    pub trace: bool,
+    /// Restricts the program to one dialect. Without one, anything in the
+    /// registry runs.
+    pub profile: Option<Profile>,
+    pub symbols: Symbols,
+}
+
+impl Context {
+    pub fn new(prog: &[i64]) -> Context {
+        Context {
+            mem: init_vmem(prog),
+            pc: 0,
+            status: Status::Running,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            rel_base: 0,
+            registry: Registry::new(),
+            coverage: None,
+            session: None,
+            calls: CallStack::default(),
+            trace: false,
+            profile: None,
+            symbols: Symbols::default(),
+        }
+    }
+
+    /// Cheap copy of the VM for trying things out: memory pages stay shared
+    /// until either side writes to them, and registered handlers are shared
+    /// outright. Devices, coverage and session recording belong to the host
+    /// and aren't carried over.
+    pub fn fork(&self) -> Context {
+        Context {
+            mem: Vmem {
+                table: self.mem.table.clone(),
+                devices: Vec::new(),
+                limits: self.mem.limits.clone(),
+                touched: self.mem.touched.clone(),
+            },
+            pc: self.pc,
+            status: self.status.clone(),
+            input: self.input.clone(),
+            output: self.output.clone(),
+            rel_base: self.rel_base,
+            registry: self.registry.clone(),
+            coverage: None,
+            session: None,
+            calls: self.calls.clone(),
+            trace: self.trace,
+            profile: self.profile,
+            symbols: self.symbols.clone(),
+        }
+    }
+}
+
+/// Describes why the VM faulted and where, if it did.
+pub fn fault_report(ctx: &Context) -> Option<String> {
+    match &ctx.status {
+        Status::Faulted(fault) => {
+            Some(format!("{}\n{}", fault, ctx.calls.describe(ctx.pc, &ctx.symbols)))
+        }
+        _ => None,
+    }
+}
+
+/// The instruction word at `pc`, which `decode` has already checked isn't
+/// negative.
+fn instr_word(ctx: &Context) -> usize {
+    let word = ctx.mem.peek(ctx.pc);
+    debug_assert!(word >= 0, "opcode checked by decode");
+    word as usize
+}
+
+/// Reads input operand `offset` (1-based) of the instruction at `pc`,
+/// following its addressing mode.
+pub fn get_arg(ctx: &mut Context, offset: usize) -> i64 {
+    let opcode = instr_word(ctx);
+    let mode = opcode / 10usize.pow(1 + offset as u32) % 10;
+
+    match mode {
+        0 => {
+            let pos = ctx.mem.peek(ctx.pc + offset) as usize;
+            ctx.mem.load(pos)
+        }
+        1 => ctx.mem.peek(ctx.pc + offset),
+        2 => {
+            let rel = ctx.mem.peek(ctx.pc + offset);
+            let addr = ctx.rel_base.checked_add(rel).expect("checked by decode");
+            ctx.mem.load(addr as usize)
+        }
+        _ => unreachable!("mode checked by decode"),
+    }
+}
+
+/// The address output operand `offset` (1-based) of the instruction at `pc`
+/// writes to.
+pub fn get_out(ctx: &Context, offset: usize) -> usize {
+    let opcode = instr_word(ctx);
+    let mode = opcode / 10usize.pow(1 + offset as u32) % 10;
+
+    match mode {
+        0 => ctx.mem.peek(ctx.pc + offset) as usize,
+        1 => ctx.pc + offset,
+        2 => {
+            let rel = ctx.mem.peek(ctx.pc + offset);
+            ctx.rel_base.checked_add(rel).expect("checked by decode") as usize
+        }
+        _ => unreachable!("mode checked by decode"),
+    }
+}
+
+/// The operands of a binary operation: two inputs and an output address.
+pub fn get_args(ctx: &mut Context) -> (i64, i64, usize) {
+    (get_arg(ctx, 1), get_arg(ctx, 2), get_out(ctx, 3))
+}
+
+fn add(ctx: &mut Context) {
+    let (lhs, rhs, out) = get_args(ctx);
+
+    match lhs.checked_add(rhs) {
+        Some(val) => ctx.mem.store(out, val),
+        None => {
+            ctx.status = Status::Faulted(Fault::ArithmeticOverflow("add", lhs, rhs));
+            return;
+        }
+    }
+    ctx.pc += 4;
+}
+
+fn mul(ctx: &mut Context) {
+    let (lhs, rhs, out) = get_args(ctx);
+
+    match lhs.checked_mul(rhs) {
+        Some(val) => ctx.mem.store(out, val),
+        None => {
+            ctx.status = Status::Faulted(Fault::ArithmeticOverflow("mul", lhs, rhs));
+            return;
+        }
+    }
+    ctx.pc += 4;
+}
+
+fn input(ctx: &mut Context) {
+    if let Some(val) = ctx.input.pop_front() {
+        let out = get_out(ctx, 1);
+        ctx.mem.store(out, val);
+        if let Some(session) = &mut ctx.session {
+            session.events.push(Event::Input(val));
+        }
+        ctx.pc += 2;
+    } else {
+        ctx.status = Status::WaitingForInput;
+    }
+}
+
+fn output(ctx: &mut Context) {
+    let val = get_arg(ctx, 1);
+
+    ctx.output.push_back(val);
+    if let Some(session) = &mut ctx.session {
+        session.events.push(Event::Output(val));
+    }
+    ctx.pc += 2;
+}
+
+fn record_branch(ctx: &mut Context, taken: bool) {
+    if let Some(cov) = &mut ctx.coverage {
+        cov.branch(ctx.pc, taken);
+    }
+}
+
+fn jump_if_true(ctx: &mut Context) {
+    let (lhs, rhs) = (get_arg(ctx, 1), get_arg(ctx, 2));
+    record_branch(ctx, lhs != 0);
+
+    if lhs != 0 {
+        ctx.pc = rhs as usize;
+    } else {
+        ctx.pc += 3;
+    }
+}
+
+fn jump_if_false(ctx: &mut Context) {
+    let (lhs, rhs) = (get_arg(ctx, 1), get_arg(ctx, 2));
+    record_branch(ctx, lhs == 0);
+
+    if lhs == 0 {
+        ctx.pc = rhs as usize;
+    } else {
+        ctx.pc += 3;
+    }
+}
+
+fn less_than(ctx: &mut Context) {
+    let (lhs, rhs, out) = get_args(ctx);
+
+    record_branch(ctx, lhs < rhs);
+
+    ctx.mem.store(out, if lhs < rhs { 1 } else { 0 });
+    ctx.pc += 4;
+}
+
+fn equals(ctx: &mut Context) {
+    let (lhs, rhs, out) = get_args(ctx);
+
+    record_branch(ctx, lhs == rhs);
+
+    ctx.mem.store(out, if lhs == rhs { 1 } else { 0 });
+    ctx.pc += 4;
+}
+
+fn adjust_rel_base(ctx: &mut Context) {
+    let adj = get_arg(ctx, 1);
+
+    match ctx.rel_base.checked_add(adj) {
+        Some(base) => ctx.rel_base = base,
+        None => {
+            ctx.status = Status::Faulted(Fault::RelBaseOverflow(ctx.rel_base, adj));
+            return;
+        }
+    }
+
+    ctx.pc += 2;
+}
+
+fn ret(ctx: &mut Context) {
+    ctx.status = Status::Halted;
+}
+
+fn debug_print(ctx: &mut Context) {
+    let val = get_arg(ctx, 1);
+
+    eprintln!("[pc {}] {}", ctx.pc, val);
+    ctx.pc += 2;
+}
+
+fn assert_equal(ctx: &mut Context) {
+    let (lhs, rhs) = (get_arg(ctx, 1), get_arg(ctx, 2));
+
+    if lhs != rhs {
+        ctx.status = Status::Faulted(Fault::AssertionFailed(lhs, rhs));
+        return;
+    }
+    ctx.pc += 3;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Param {
+    In,
+    Out,
+}
+
+const BINARY: &[Param] = &[Param::In, Param::In, Param::Out];
+const JUMP: &[Param] = &[Param::In, Param::In];
+
+/// Carries out one instruction: reads its operands with `get_arg` and
+/// `get_out`, does its work and advances `pc`. Host callbacks can keep
+/// state of their own in the closure. Handlers are `Send` so a VM and its
+/// forks can run on other threads.
+pub type Handler = Box<dyn FnMut(&mut Context) + Send>;
+
+#[derive(Clone)]
+pub struct Instr {
+    pub name: &'static str,
+    pub params: &'static [Param],
+    pub exec: Arc<Mutex<Handler>>,
+}
+
+#[derive(Clone)]
+pub struct Registry {
+    pub instrs: AddrMap<Instr>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        let mut res = Registry {
+            instrs: AddrMap::default(),
+        };
+
+        res.register(1, "add", BINARY, Box::new(add));
+        res.register(2, "mul", BINARY, Box::new(mul));
+        res.register(3, "in", &[Param::Out], Box::new(input));
+        res.register(4, "out", &[Param::In], Box::new(output));
+        res.register(5, "jnz", JUMP, Box::new(jump_if_true));
+        res.register(6, "jz", JUMP, Box::new(jump_if_false));
+        res.register(7, "lt", BINARY, Box::new(less_than));
+        res.register(8, "eq", BINARY, Box::new(equals));
+        res.register(9, "arb", &[Param::In], Box::new(adjust_rel_base));
+        res.register(99, "halt", &[], Box::new(ret));
+
+        res
+    }
+
+    pub fn register(
+        &mut self,
+        opcode: usize,
+        name: &'static str,
+        params: &'static [Param],
+        exec: Handler,
+    ) {
+        if opcode == 0 || opcode > 99 {
+            panic!("Opcode out of range: {}", opcode);
+        }
+        if params.len() > 3 {
+            panic!("Too many parameters for {}: {}", name, params.len());
+        }
+        if let Some(prev) = self.instrs.get(&opcode) {
+            panic!("Opcode {} already registered as {}", opcode, prev.name);
+        }
+
+        self.instrs.insert(
+            opcode,
+            Instr {
+                name,
+                params,
+                exec: Arc::new(Mutex::new(exec)),
+            },
+        );
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+/// Host calls for instrumented programs, enabled with `--ext`.
+pub fn register_ext(registry: &mut Registry) {
+    registry.register(50, "dbg", &[Param::In], Box::new(debug_print));
+    registry.register(51, "assert", &[Param::In, Param::In], Box::new(assert_equal));
+}
+
+fn decode(ctx: &Context) -> Result<Arc<Mutex<Handler>>, Fault> {
+    let word = ctx.mem.peek(ctx.pc);
+    if word < 0 {
+        return Err(Fault::UnknownOpcode(word));
+    }
+    let opcode = word as usize;
+    let operation = opcode % 100;
+
+    if let Some(profile) = ctx.profile {
+        if !profile.allows_opcode(operation) {
+            return Err(Fault::OpcodeNotInProfile(profile, operation));
+        }
+    }
+    let instr = match ctx.registry.instrs.get(&operation) {
+        Some(instr) => instr,
+        None => return Err(Fault::UnknownOpcode(operation as i64)),
+    };
+
+    for (i, param) in instr.params.iter().enumerate() {
+        let mode = opcode / 10usize.pow(2 + i as u32) % 10;
+        if mode > 2 {
+            return Err(Fault::InvalidMode(mode));
+        }
+        if let Some(profile) = ctx.profile {
+            if !profile.allows_mode(mode) {
+                return Err(Fault::ModeNotInProfile(profile, mode));
+            }
+        }
+        if *param == Param::Out && mode == 1 {
+            return Err(Fault::ImmediateOutput(instr.name));
+        }
+
+        let word = ctx.mem.peek(ctx.pc + 1 + i);
+        match mode {
+            0 => ctx.mem.check(word, *param == Param::Out)?,
+            2 => {
+                let addr = ctx
+                    .rel_base
+                    .checked_add(word)
+                    .ok_or(Fault::RelBaseOverflow(ctx.rel_base, word))?;
+                ctx.mem.check(addr, *param == Param::Out)?
+            }
+            _ => (),
+        }
+    }
+
+    Ok(instr.exec.clone())
+}
+
+fn trace(ctx: &Context) {
+    let words: Vec<i64> = (ctx.pc..ctx.pc + 4).map(|a| ctx.mem.peek(a)).collect();
+    let text = match disasm(&ctx.registry, &ctx.symbols, &words, 0) {
+        Some((text, _)) => text,
+        None => format!(".word {}", words[0]),
+    };
+    let comment = match ctx.symbols.comment(ctx.pc) {
+        Some(comment) => format!("  ; {}", comment),
+        None => String::new(),
+    };
+    if let Some(name) = ctx.symbols.func(ctx.pc) {
+        eprintln!("{:>6}  {}{}:", "", "  ".repeat(ctx.calls.depth()), name);
+    }
+
+    eprintln!(
+        "{:>6}  {}{}{}",
+        ctx.pc,
+        "  ".repeat(ctx.calls.depth()),
+        text,
+        comment
+    );
+}
+
+pub fn step(ctx: &mut Context) {
+    let exec = match decode(ctx) {
+        Ok(exec) => exec,
+        Err(fault) => {
+            ctx.status = Status::Faulted(fault);
+            return;
+        }
+    };
+    let pc = ctx.pc;
+    let opcode = ctx.mem.peek(pc) as usize % 100;
+    let rel_base = ctx.rel_base;
+    if ctx.trace {
+        trace(ctx);
+    }
+    // A handler that panicked is still usable; the host decides what a
+    // panic means for the VM.
+    (exec.lock().unwrap_or_else(|e| e.into_inner()))(ctx);
+    callstack::track(ctx, opcode, pc, rel_base);
+
+    // A blocked input is retried once input arrives, so count it then.
+    if let Some(cov) = &mut ctx.coverage {
+        if ctx.status != Status::WaitingForInput {
+            cov.visit(pc);
+        }
+    }
+}
+
+pub fn run(ctx: &mut Context) {
+    while ctx.status == Status::Running {
+        step(ctx);
+    }
+}
+
+pub fn add_input(ctx: &mut Context, val: i64) {
+    ctx.input.push_back(val);
+    if ctx.status == Status::WaitingForInput {
+        ctx.status = Status::Running;
+    }
+}
@@ -1,256 +1,212 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::VecDeque;
+use std::env::args;
+use std::fs::{self, File};
 use std::io::{self, BufRead};
-use std::ops::{Index, IndexMut};
+use std::process;
+use std::time::Instant;
+
+use intcode::coverage::Coverage;
+use intcode::session::Session;
+use intcode::symbols::Symbols;
+use intcode::topology::Topology;
+use intcode::{
+    add_input, console, fault_report, gdb, register_ext, run, scan, Clock, Context, Status,
+    Watch,
+};
 
-const PAGE_SIZE: usize = 32768;
-
-#[derive(PartialEq)]
-enum Status {
-    Running,
-    WaitingForInput,
-    Halted,
-}
-
-type Page = [i64; PAGE_SIZE];
-struct Vmem {
-    table: HashMap<usize, Page>
+fn get_input() -> i64 {
+    io::stdin().lock().lines().next().expect("No input").unwrap().parse::<i64>().expect("invalid int")
 }
 
-impl Index<usize> for Vmem {
-    type Output = i64;
-
-    fn index(&self, idx: usize) -> &Self::Output {
-        let page_num = idx / PAGE_SIZE;
-        let page_off = idx % PAGE_SIZE;
-
-        &self.table[&page_num][page_off]
-    }
+fn parse_addr(arg: Option<String>) -> usize {
+    arg.expect("Missing address")
+        .parse::<usize>()
+        .expect("invalid address")
 }
 
-impl IndexMut<usize> for Vmem {
-    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
-        let page_num = idx / PAGE_SIZE;
-        let page_off = idx % PAGE_SIZE;
-
-        if !self.table.contains_key(&page_num) {
-            self.table.insert(page_num, [0; PAGE_SIZE]);
-        }
-
-        &mut self.table.get_mut(&page_num).unwrap()[page_off]
+fn verify_replay(expected: &[i64], actual: &[i64]) {
+    if let Some(i) = (0..expected.len().min(actual.len())).find(|&i| expected[i] != actual[i]) {
+        eprintln!(
+            "Replay diverged at output {}: expected {}, got {}",
+            i, expected[i], actual[i]
+        );
+        process::exit(1);
     }
-}
-
-fn init_vmem(prog: &Vec<i64>) -> Vmem {
-    let mut res = Vmem { table: HashMap::new() };
-
-    for i in 0..prog.len() {
-        res[i] = prog[i];
+    if expected.len() != actual.len() {
+        eprintln!(
+            "Replay produced {} outputs, session recorded {}",
+            actual.len(),
+            expected.len()
+        );
+        process::exit(1);
     }
 
-    res
+    eprintln!("Replay matched {} outputs", actual.len());
 }
 
-struct Context {
-    mem: Vmem,
-    pc: usize,
-    status: Status,
-    input: VecDeque<i64>,
-    output: VecDeque<i64>,
-    rel_base: i64,
+fn parse_prog(line: &str) -> Vec<i64> {
+    line.trim()
+        .split(',')
+        .map(|w| w.parse::<i64>().expect("invalid int"))
+        .collect()
 }
 
-fn get_arg(ctx: &Context, offset: usize) -> i64 {
-    let opcode = ctx.mem[ctx.pc] as usize;
-    let mode = opcode / 10usize.pow(1 + offset as u32) % 10;
-
-    match mode {
-        0 => {
-            let pos = ctx.mem[ctx.pc + offset] as usize;
-            ctx.mem[pos]
-        }
-        1 => ctx.mem[ctx.pc + offset],
-        2 => {
-            let rel = ctx.mem[ctx.pc + offset];
-            ctx.mem[(ctx.rel_base + rel) as usize]
-        }
-        _ => panic!("Invalid mode"),
-    }
+fn read_prog() -> Vec<i64> {
+    parse_prog(&io::stdin().lock().lines().next().expect("No input").unwrap())
 }
 
-fn get_out(ctx: &mut Context, offset: usize) -> &mut i64 {
-    let opcode = ctx.mem[ctx.pc] as usize;
-    let mode = opcode / 10usize.pow(1 + offset as u32) % 10;
+fn main() {
+    let mut opts: Vec<String> = args().skip(1).collect();
+
+    // A topology brings its own programs and inputs.
+    if opts.first().map(|opt| opt.as_str()) == Some("--topology") {
+        let mut topology = Topology::load(opts.get(1).expect("Missing topology file"));
+        topology.run();
+        print!("{}", topology.report());
+        return;
+    }
 
-    match mode {
-        0 => {
-            let pos = ctx.mem[ctx.pc + offset] as usize;
-            &mut ctx.mem[pos]
+    // A replayed session supplies the program, VM options and inputs.
+    let replay = opts
+        .iter()
+        .position(|opt| opt == "--replay")
+        .map(|i| Session::load(opts.get(i + 1).expect("Missing session file")));
+    let prog = match &replay {
+        Some(session) => {
+            opts.extend(session.opts.iter().cloned());
+            session.prog.clone()
         }
-        1 => &mut ctx.mem[ctx.pc + offset],
-        2 => {
-            let rel = ctx.mem[ctx.pc + offset];
-            &mut ctx.mem[(ctx.rel_base + rel) as usize]
+        None => match opts.iter().position(|opt| opt == "--program") {
+            Some(i) => {
+                let path = opts.get(i + 1).expect("Missing program file");
+                let contents = fs::read_to_string(path).expect("Unable to read program");
+                parse_prog(contents.lines().next().expect("Empty program"))
+            }
+            None => read_prog(),
+        },
+    };
+    let mut recorded_inputs = replay.as_ref().map(|s| VecDeque::from(s.inputs()));
+
+    let mut ctx = Context::new(&prog);
+    let mut coverage_file = None;
+    let mut session_file = None;
+    let mut gdb_port = None;
+    let mut console = false;
+    let mut scanner = false;
+    let mut pages = false;
+    let mut transcript = None;
+    let mut vm_opts = Vec::new();
+
+    let mut opts = opts.into_iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--ext" => {
+                register_ext(&mut ctx.registry);
+                vm_opts.push(opt);
+            }
+            "--clock" => {
+                let addr = parse_addr(opts.next());
+                let clock = Clock {
+                    start: Instant::now(),
+                };
+                ctx.mem.map(addr, 1, Box::new(clock));
+                vm_opts.extend(vec![opt, addr.to_string()]);
+            }
+            "--watch" => {
+                let addr = parse_addr(opts.next());
+                let watch = Watch {
+                    base: addr,
+                    cells: vec![ctx.mem.load(addr)],
+                };
+                ctx.mem.map(addr, 1, Box::new(watch));
+                vm_opts.extend(vec![opt, addr.to_string()]);
+            }
+            "--coverage" => {
+                coverage_file = Some(opts.next().expect("Missing coverage file"));
+                ctx.coverage = Some(Coverage::new(&prog));
+            }
+            "--gdb" => {
+                let port = opts.next().expect("Missing gdb port");
+                gdb_port = Some(port.parse::<u16>().expect("invalid port"));
+            }
+            "--record" => session_file = Some(opts.next().expect("Missing session file")),
+            "--console" => console = true,
+            "--scan" => scanner = true,
+            "--trace" => ctx.trace = true,
+            "--max-addr" => {
+                let max = parse_addr(opts.next());
+                ctx.mem.limits.max_addr = Some(max);
+                vm_opts.extend(vec![opt, max.to_string()]);
+            }
+            "--max-pages" => {
+                let max = opts.next().expect("Missing page count");
+                ctx.mem.limits.max_pages = Some(max.parse::<usize>().expect("invalid page count"));
+                vm_opts.extend(vec![opt, max]);
+            }
+            "--pages" => pages = true,
+            "--symbols" => {
+                ctx.symbols = Symbols::load(&opts.next().expect("Missing symbol file"));
+            }
+            "--profile" => {
+                let profile = opts.next().expect("Missing profile");
+                ctx.profile = Some(profile.parse().unwrap_or_else(|e| panic!("{}", e)));
+                vm_opts.extend(vec![opt, profile]);
+            }
+            "--transcript" => {
+                let path = opts.next().expect("Missing transcript file");
+                transcript = Some(File::create(path).expect("Unable to create transcript"));
+            }
+            "--program" | "--replay" => {
+                opts.next();
+            }
+            _ => panic!("Unknown option: {}", opt),
         }
-        _ => panic!("Invalid mode"),
-    }
-}
-
-fn get_args(ctx: &mut Context) -> (i64, i64, &mut i64) {
-    (
-        get_arg(ctx, 1),
-        get_arg(ctx, 2),
-        get_out(ctx, 3),
-    )
-}
-
-fn add(ctx: &mut Context) {
-    let (lhs, rhs, out) = get_args(ctx);
-
-    *out = lhs + rhs;
-    ctx.pc += 4;
-}
-
-fn mul(ctx: &mut Context) {
-    let (lhs, rhs, out) = get_args(ctx);
-
-    *out = lhs * rhs;
-    ctx.pc += 4;
-}
-
-fn input(ctx: &mut Context) {
-    if let Some(val) = ctx.input.pop_front() {
-        let out = get_out(ctx, 1);
-        *out = val;
-        ctx.pc += 2;
-    } else {
-        ctx.status = Status::WaitingForInput;
-    }
-}
-
-fn output(ctx: &mut Context) {
-    let val = get_arg(ctx, 1);
-
-    ctx.output.push_back(val);
-    ctx.pc += 2;
-}
-
-fn jump_if_true(ctx: &mut Context) {
-    let (lhs, rhs) = (get_arg(ctx, 1), get_arg(ctx, 2));
-
-    if lhs != 0 {
-        ctx.pc = rhs as usize;
-    } else {
-        ctx.pc += 3;
     }
-}
-
-fn jump_if_false(ctx: &mut Context) {
-    let (lhs, rhs) = (get_arg(ctx, 1), get_arg(ctx, 2));
-
-    if lhs == 0 {
-        ctx.pc = rhs as usize;
-    } else {
-        ctx.pc += 3;
+    if session_file.is_some() {
+        ctx.session = Some(Session::new(&prog, &vm_opts));
     }
-}
 
-fn less_than(ctx: &mut Context) {
-    let (lhs, rhs, out) = get_args(ctx);
+    let mut next_input = || match &mut recorded_inputs {
+        Some(inputs) => inputs.pop_front().expect("Session ran out of input"),
+        None => get_input(),
+    };
 
-    if lhs < rhs {
-        *out = 1;
+    if console {
+        console::run_console(&mut ctx, transcript);
+    } else if scanner {
+        scan::run_scanner(&mut ctx);
     } else {
-        *out = 0;
+        if let Some(port) = gdb_port {
+            gdb::serve(&mut ctx, port, &mut next_input);
+        }
+        loop {
+            run(&mut ctx);
+            if ctx.status != Status::WaitingForInput {
+                break;
+            }
+            add_input(&mut ctx, next_input());
+        }
     }
-    ctx.pc += 4;
-}
-
-fn equals(ctx: &mut Context) {
-    let (lhs, rhs, out) = get_args(ctx);
 
-    if lhs == rhs {
-        *out = 1;
-    } else {
-        *out = 0;
+    let outputs: Vec<i64> = ctx.output.drain(..).collect();
+    for val in &outputs {
+        println!("{}", val);
     }
-    ctx.pc += 4;
-}
-
-fn adjust_rel_base(ctx: &mut Context) {
-    let adj = get_arg(ctx, 1);
-
-    ctx.rel_base += adj;
-
-    ctx.pc += 2;
-}
 
-fn ret(ctx: &mut Context) {
-    ctx.status = Status::Halted;
-}
-
-fn decode(contents: &Vmem, pc: usize) -> fn(&mut Context) {
-    let opcode = contents[pc] as usize;
-    let operation = opcode % 100;
-
-    match operation {
-        1 => add,
-        2 => mul,
-        3 => input,
-        4 => output,
-        5 => jump_if_true,
-        6 => jump_if_false,
-        7 => less_than,
-        8 => equals,
-        9 => adjust_rel_base,
-        99 => ret,
-        _ => panic!("Unrecognized opcode: {}", operation),
+    if let (Some(path), Some(cov)) = (coverage_file, &ctx.coverage) {
+        fs::write(path, cov.report(&ctx.registry, &ctx.symbols)).expect("Unable to write coverage report");
     }
-}
-
-fn run(ctx: &mut Context) {
-    while ctx.status == Status::Running {
-        let fun = decode(&ctx.mem, ctx.pc);
-        fun(ctx);
+    if let (Some(path), Some(session)) = (session_file, &ctx.session) {
+        session.save(&path);
     }
-}
-
-fn get_input() -> i64 {
-    io::stdin().lock().lines().next().expect("No input").unwrap().parse::<i64>().expect("invalid int")
-}
-
-fn add_input(ctx: &mut Context, val: i64) {
-    ctx.input.push_back(val);
-    if ctx.status == Status::WaitingForInput {
-        ctx.status = Status::Running;
+    if pages {
+        eprint!("{}", ctx.mem.page_report());
     }
-}
-
-fn main() {
-    let prog: Vec<i64> = io::stdin()
-        .lock()
-        .lines()
-        .next()
-        .expect("No input")
-        .unwrap()
-        .split(',')
-        .map(|w| w.parse::<i64>().expect("invalid int"))
-        .collect();
-
-    let mut ctx = Context {
-        mem: init_vmem(&prog),
-        pc: 0,
-        status: Status::Running,
-        input: VecDeque::new(),
-        output: VecDeque::new(),
-        rel_base: 0,
-    };
-    while ctx.status != Status::Halted {
-        run(&mut ctx);
-        if ctx.status == Status::WaitingForInput {
-            add_input(&mut ctx, get_input());
-        }
+    if let Some(session) = replay {
+        verify_replay(&session.outputs(), &outputs);
     }
-    while let Some(val) = ctx.output.pop_front() {
-        println!("{}", val);
+    if let Some(report) = fault_report(&ctx) {
+        eprint!("{}", report);
+        process::exit(1);
     }
 }
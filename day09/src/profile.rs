@@ -0,0 +1,54 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The Intcode dialects as the puzzles introduced them. Day 2 has only
+/// add, mul and halt in position mode; day 5 adds I/O, jumps, comparisons
+/// and immediate mode; day 9 adds `arb` and relative mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    Day2,
+    Day5,
+    Day9,
+}
+
+impl Profile {
+    pub fn allows_opcode(self, opcode: usize) -> bool {
+        match self {
+            Profile::Day2 => matches!(opcode, 1 | 2 | 99),
+            Profile::Day5 => matches!(opcode, 1..=8 | 99),
+            Profile::Day9 => matches!(opcode, 1..=9 | 99),
+        }
+    }
+
+    pub fn allows_mode(self, mode: usize) -> bool {
+        match self {
+            Profile::Day2 => mode == 0,
+            Profile::Day5 => mode <= 1,
+            Profile::Day9 => mode <= 2,
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Profile::Day2 => "day2",
+            Profile::Day5 => "day5",
+            Profile::Day9 => "day9",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Profile, String> {
+        match s {
+            "day2" => Ok(Profile::Day2),
+            "day5" | "day7" => Ok(Profile::Day5),
+            "day9" | "full" => Ok(Profile::Day9),
+            _ => Err(format!("Unknown profile: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+use crate::{add_input, run, AddrMap, Context, Page, Status, PAGE_SIZE};
+
+/// Most candidates shown by `list`.
+const LIST_LIMIT: usize = 32;
+
+const HELP: &str = "\
+input V...     queue input values
+run            run until the program needs more input or halts
+poke ADDR VAL  write VAL to ADDR
+changed        keep addresses whose value changed since the last filter
+unchanged      keep addresses whose value did not change
+increased      keep addresses whose value went up
+decreased      keep addresses whose value went down
+eq N           keep addresses that currently hold N
+list           show the remaining candidates
+reset          start over with every address as a candidate
+quit           leave the scanner
+";
+
+/// Narrows down memory addresses by how their values change between
+/// snapshots, for finding where a program keeps its state.
+struct Scanner {
+    snapshot: AddrMap<Arc<Page>>,
+    candidates: Option<BTreeSet<usize>>,
+}
+
+fn peek(table: &AddrMap<Arc<Page>>, addr: usize) -> i64 {
+    table.get(&(addr / PAGE_SIZE)).map_or(0, |page| page[addr % PAGE_SIZE])
+}
+
+impl Scanner {
+    fn reset(&mut self, ctx: &Context) {
+        self.snapshot = ctx.mem.table.clone();
+        self.candidates = None;
+    }
+
+    fn filter(&mut self, ctx: &Context, keep: impl Fn(i64, i64) -> bool) {
+        let addrs: BTreeSet<usize> = match self.candidates.take() {
+            Some(addrs) => addrs,
+            None => ctx
+                .mem
+                .table
+                .keys()
+                .chain(self.snapshot.keys())
+                .flat_map(|page_num| page_num * PAGE_SIZE..(page_num + 1) * PAGE_SIZE)
+                .collect(),
+        };
+
+        let kept: BTreeSet<usize> = addrs
+            .into_iter()
+            .filter(|&addr| keep(peek(&self.snapshot, addr), ctx.mem.peek(addr)))
+            .collect();
+
+        println!("{} candidates", kept.len());
+        self.candidates = Some(kept);
+        self.snapshot = ctx.mem.table.clone();
+    }
+
+    fn list(&self, ctx: &Context) {
+        match &self.candidates {
+            Some(addrs) => {
+                for addr in addrs.iter().take(LIST_LIMIT) {
+                    println!("[{}] = {}", addr, ctx.mem.peek(*addr));
+                }
+                if addrs.len() > LIST_LIMIT {
+                    println!("... {} more", addrs.len() - LIST_LIMIT);
+                }
+            }
+            None => println!("No filters applied yet"),
+        }
+    }
+}
+
+fn run_until_blocked(ctx: &mut Context, pending: &mut Vec<i64>) {
+    loop {
+        run(ctx);
+        if ctx.status != Status::WaitingForInput || pending.is_empty() {
+            break;
+        }
+        add_input(ctx, pending.remove(0));
+    }
+
+    let outputs: Vec<String> = ctx.output.drain(..).map(|v| v.to_string()).collect();
+    let status = match &ctx.status {
+        Status::Running => String::from("running"),
+        Status::WaitingForInput => String::from("waiting for input"),
+        Status::Halted => String::from("halted"),
+        Status::Faulted(fault) => format!("faulted: {}", fault),
+    };
+    println!("pc {}, {}, {} outputs", ctx.pc, status, outputs.len());
+    if !outputs.is_empty() {
+        let tail = &outputs[outputs.len().saturating_sub(6)..];
+        println!("last outputs: {}", tail.join(","));
+    }
+}
+
+fn parse(word: Option<&str>) -> Option<i64> {
+    word.and_then(|w| w.parse::<i64>().ok())
+}
+
+/// Reads scanner commands from stdin until `quit` or end of input.
+pub fn run_scanner(ctx: &mut Context) {
+    let mut scanner = Scanner {
+        snapshot: AddrMap::default(),
+        candidates: None,
+    };
+    let mut pending = Vec::new();
+    scanner.reset(ctx);
+
+    let stdin = io::stdin();
+    loop {
+        print!("scan> ");
+        io::stdout().flush().unwrap();
+
+        let line = match stdin.lock().lines().next() {
+            Some(line) => line.unwrap(),
+            None => break,
+        };
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("input") => pending.extend(words.filter_map(|w| w.parse::<i64>().ok())),
+            Some("run") => run_until_blocked(ctx, &mut pending),
+            Some("poke") => match (parse(words.next()), parse(words.next())) {
+                (Some(addr), Some(val)) if addr >= 0 => ctx.mem.store(addr as usize, val),
+                _ => println!("usage: poke ADDR VAL"),
+            },
+            Some("changed") => scanner.filter(ctx, |old, new| old != new),
+            Some("unchanged") => scanner.filter(ctx, |old, new| old == new),
+            Some("increased") => scanner.filter(ctx, |old, new| new > old),
+            Some("decreased") => scanner.filter(ctx, |old, new| new < old),
+            Some("eq") => match parse(words.next()) {
+                Some(n) => scanner.filter(ctx, |_, new| new == n),
+                None => println!("usage: eq N"),
+            },
+            Some("list") => scanner.list(ctx),
+            Some("reset") => scanner.reset(ctx),
+            Some("quit") => break,
+            None => (),
+            Some(_) => print!("{}", HELP),
+        }
+    }
+}
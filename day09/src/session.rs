@@ -0,0 +1,89 @@
+use std::fs;
+
+pub enum Event {
+    Input(i64),
+    Output(i64),
+}
+
+/// A recorded run: the program image, the options that configured the VM
+/// and every value the program consumed or produced, in order.
+pub struct Session {
+    pub prog: Vec<i64>,
+    pub opts: Vec<String>,
+    pub events: Vec<Event>,
+}
+
+fn parse_int(val: &str) -> i64 {
+    val.parse::<i64>().expect("invalid int")
+}
+
+impl Session {
+    pub fn new(prog: &[i64], opts: &[String]) -> Session {
+        Session {
+            prog: prog.to_vec(),
+            opts: opts.to_vec(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Session {
+        let contents = fs::read_to_string(path).expect("Unable to read session");
+        let mut res = Session::new(&[], &[]);
+
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("program") => {
+                    let prog = words.next().expect("Missing program");
+                    res.prog = prog.split(',').map(parse_int).collect();
+                }
+                Some("options") => res.opts.extend(words.map(String::from)),
+                Some("input") => res.events.push(Event::Input(parse_int(words.next().unwrap()))),
+                Some("output") => res.events.push(Event::Output(parse_int(words.next().unwrap()))),
+                Some(word) if word.starts_with('#') => (),
+                None => (),
+                Some(word) => panic!("Invalid session entry: {}", word),
+            }
+        }
+
+        res
+    }
+
+    pub fn save(&self, path: &str) {
+        let mut contents = String::from("# intcode session\n");
+
+        let prog: Vec<String> = self.prog.iter().map(|v| v.to_string()).collect();
+        contents += &format!("program {}\n", prog.join(","));
+        if !self.opts.is_empty() {
+            contents += &format!("options {}\n", self.opts.join(" "));
+        }
+        for event in &self.events {
+            contents += &match event {
+                Event::Input(val) => format!("input {}\n", val),
+                Event::Output(val) => format!("output {}\n", val),
+            };
+        }
+
+        fs::write(path, contents).expect("Unable to write session");
+    }
+
+    pub fn inputs(&self) -> Vec<i64> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Input(val) => Some(*val),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<i64> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Output(val) => Some(*val),
+                _ => None,
+            })
+            .collect()
+    }
+}
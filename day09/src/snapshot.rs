@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use crate::callstack::CallStack;
+use crate::{Context, Status, PAGE_SIZE};
+
+fn join(vals: &[i64]) -> String {
+    vals.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn split(vals: Option<&str>) -> Result<Vec<i64>, String> {
+    match vals {
+        Some(vals) => vals
+            .split(',')
+            .map(|w| w.parse::<i64>().map_err(|_| format!("invalid int: {}", w)))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse<T: FromStr>(word: Option<&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("missing {}", what))?;
+    word.parse()
+        .map_err(|_| format!("invalid {}: {}", what, word))
+}
+
+/// Serializes the execution state of a VM: registers, pending I/O and every
+/// page of memory, with trailing zeros trimmed. Devices and registered
+/// opcodes belong to the host and are left out.
+pub fn save(ctx: &Context) -> String {
+    let mut res = String::from("# intcode snapshot\n");
+
+    res += &format!("pc {}\n", ctx.pc);
+    res += &format!("rel_base {}\n", ctx.rel_base);
+    res += match ctx.status {
+        Status::Running => "status running\n",
+        Status::WaitingForInput => "status waiting\n",
+        Status::Halted => "status halted\n",
+        Status::Faulted(_) => "status faulted\n",
+    };
+    res += &format!("input {}\n", join(&Vec::from(ctx.input.clone())));
+    res += &format!("output {}\n", join(&Vec::from(ctx.output.clone())));
+
+    let mut page_nums: Vec<&usize> = ctx.mem.table.keys().collect();
+    page_nums.sort();
+    for page_num in page_nums {
+        let page = &ctx.mem.table[page_num];
+        let len = page.iter().rposition(|&v| v != 0).map_or(0, |i| i + 1);
+        res += &format!("page {} {}\n", page_num, join(&page[..len]));
+    }
+
+    res
+}
+
+/// Restores the execution state written by `save`, replacing all memory and
+/// forgetting the call stack.
+/// The snapshot is parsed in full first, so a bad one leaves the VM as it
+/// was.
+pub fn load(ctx: &mut Context, snapshot: &str) -> Result<(), String> {
+    let mut pc = ctx.pc;
+    let mut rel_base = ctx.rel_base;
+    let mut status = None;
+    let mut input = VecDeque::new();
+    let mut output = VecDeque::new();
+    let mut pages = Vec::new();
+
+    for line in snapshot.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("pc") => pc = parse(words.next(), "pc")?,
+            Some("rel_base") => rel_base = parse(words.next(), "rel_base")?,
+            Some("status") => {
+                status = Some(match words.next() {
+                    Some("running") => Status::Running,
+                    Some("waiting") => Status::WaitingForInput,
+                    Some("halted") => Status::Halted,
+                    // Faults depend only on the state saved here, so
+                    // resuming hits the same one again.
+                    Some("faulted") => Status::Running,
+                    status => return Err(format!("invalid status: {:?}", status)),
+                })
+            }
+            Some("input") => input = VecDeque::from(split(words.next())?),
+            Some("output") => output = VecDeque::from(split(words.next())?),
+            Some("page") => {
+                let page_num: usize = parse(words.next(), "page")?;
+                let base = page_num
+                    .checked_mul(PAGE_SIZE)
+                    .ok_or_else(|| format!("page {} is out of range", page_num))?;
+                let vals = split(words.next())?;
+                if vals.len() > PAGE_SIZE {
+                    return Err(format!("page {} is too long", page_num));
+                }
+                pages.push((base, vals));
+            }
+            Some(word) if word.starts_with('#') => (),
+            None => (),
+            Some(word) => return Err(format!("invalid snapshot entry: {}", word)),
+        }
+    }
+
+    // Call frames and page usage describe the timeline being abandoned.
+    ctx.calls = CallStack::default();
+    ctx.mem.touched.clear();
+    ctx.mem.table.clear();
+    for (base, vals) in pages {
+        ctx.mem[base] = 0;
+        for (off, val) in vals.into_iter().enumerate() {
+            ctx.mem[base + off] = val;
+        }
+    }
+    ctx.pc = pc;
+    ctx.rel_base = rel_base;
+    if let Some(status) = status {
+        ctx.status = status;
+    }
+    ctx.input = input;
+    ctx.output = output;
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// Names for the parts of a program someone has reverse engineered, kept in
+/// a sidecar file next to it:
+///
+/// ```text
+/// # intcode symbols
+/// func 1200 draw_tile
+/// var 386 score
+/// data 639 880 tiles
+/// comment 1215 skip walls
+/// ```
+///
+/// `data` takes a start address and a length; addresses inside the table
+/// are shown as `tiles+N`.
+#[derive(Clone, Default)]
+pub struct Symbols {
+    funcs: BTreeMap<usize, String>,
+    data: BTreeMap<usize, (usize, String)>,
+    comments: HashMap<usize, String>,
+}
+
+fn parse_addr(word: Option<&str>, line: &str) -> usize {
+    word.and_then(|w| w.parse::<usize>().ok())
+        .unwrap_or_else(|| panic!("Invalid symbol entry: {}", line))
+}
+
+fn parse_name(word: Option<&str>, line: &str) -> String {
+    word.unwrap_or_else(|| panic!("Invalid symbol entry: {}", line))
+        .to_string()
+}
+
+impl Symbols {
+    pub fn load(path: &str) -> Symbols {
+        let contents = fs::read_to_string(path).expect("Unable to read symbol file");
+        let mut res = Symbols::default();
+
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("func") => {
+                    let addr = parse_addr(words.next(), line);
+                    res.funcs.insert(addr, parse_name(words.next(), line));
+                }
+                Some("var") => {
+                    let addr = parse_addr(words.next(), line);
+                    res.data.insert(addr, (1, parse_name(words.next(), line)));
+                }
+                Some("data") => {
+                    let addr = parse_addr(words.next(), line);
+                    let len = parse_addr(words.next(), line);
+                    res.data.insert(addr, (len, parse_name(words.next(), line)));
+                }
+                Some("comment") => {
+                    let addr = parse_addr(words.next(), line);
+                    let text: Vec<&str> = words.collect();
+                    res.comments.insert(addr, text.join(" "));
+                }
+                Some(word) if word.starts_with('#') => (),
+                None => (),
+                Some(_) => panic!("Invalid symbol entry: {}", line),
+            }
+        }
+
+        res
+    }
+
+    /// The function starting exactly at `addr`.
+    pub fn func(&self, addr: usize) -> Option<&str> {
+        self.funcs.get(&addr).map(|name| name.as_str())
+    }
+
+    /// A code address relative to the function containing it, e.g.
+    /// `draw_tile+5`.
+    pub fn locate(&self, addr: usize) -> Option<String> {
+        let (start, name) = self.funcs.range(..=addr).next_back()?;
+        if *start == addr {
+            Some(name.clone())
+        } else {
+            Some(format!("{}+{}", name, addr - start))
+        }
+    }
+
+    /// A data address as a variable or an offset into a table.
+    pub fn data(&self, addr: usize) -> Option<String> {
+        let (start, (len, name)) = self.data.range(..=addr).next_back()?;
+        if *start == addr {
+            Some(name.clone())
+        } else if addr < start + len {
+            Some(format!("{}+{}", name, addr - start))
+        } else {
+            None
+        }
+    }
+
+    pub fn comment(&self, addr: usize) -> Option<&str> {
+        self.comments.get(&addr).map(|text| text.as_str())
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::{add_input, fault_report, run, Context, Status};
+
+/// A set of VMs wired together, read from a topology file:
+///
+/// ```text
+/// # intcode topology
+/// vm a input.txt 9,0
+/// vm b input.txt 8
+/// link a b
+/// link b a
+/// output thrust b last
+/// ```
+///
+/// `vm NAME PROGRAM [INPUTS]` starts a VM on a program, relative to the
+/// topology file, with initial inputs such as a phase setting. `link FROM TO`
+/// sends everything FROM outputs to TO; a VM with several links out sends
+/// each value down all of them, and one with several links in reads values
+/// in the order they were sent. `output NAME VM [all|last]` collects what a
+/// VM outputs under a name.
+pub struct Topology {
+    names: Vec<String>,
+    vms: Vec<Context>,
+    links: Vec<Vec<usize>>,
+    outputs: Vec<Tap>,
+}
+
+struct Tap {
+    name: String,
+    vm: usize,
+    last_only: bool,
+    vals: Vec<i64>,
+}
+
+fn parse_vals(word: &str) -> Vec<i64> {
+    word.split(',')
+        .map(|w| w.parse::<i64>().expect("invalid int"))
+        .collect()
+}
+
+impl Topology {
+    pub fn load(path: &str) -> Topology {
+        let contents = fs::read_to_string(path).expect("Unable to read topology file");
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut res = Topology {
+            names: Vec::new(),
+            vms: Vec::new(),
+            links: Vec::new(),
+            outputs: Vec::new(),
+        };
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let lookup = |ids: &HashMap<String, usize>, name: Option<&str>| -> usize {
+            let name = name.expect("Missing VM name");
+            *ids.get(name)
+                .unwrap_or_else(|| panic!("Unknown VM: {}", name))
+        };
+
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("vm") => {
+                    let name = words.next().expect("Missing VM name");
+                    let prog_path = dir.join(words.next().expect("Missing program"));
+                    let prog = fs::read_to_string(&prog_path)
+                        .unwrap_or_else(|e| panic!("Unable to read {:?}: {}", prog_path, e));
+                    let line = prog.lines().next().expect("Empty program");
+
+                    let mut ctx = Context::new(&parse_vals(line.trim()));
+                    for val in words.next().map(parse_vals).unwrap_or_default() {
+                        add_input(&mut ctx, val);
+                    }
+
+                    if ids.insert(name.to_string(), res.vms.len()).is_some() {
+                        panic!("Duplicate VM: {}", name);
+                    }
+                    res.names.push(name.to_string());
+                    res.vms.push(ctx);
+                    res.links.push(Vec::new());
+                }
+                Some("link") => {
+                    let from = lookup(&ids, words.next());
+                    let to = lookup(&ids, words.next());
+                    res.links[from].push(to);
+                }
+                Some("output") => {
+                    let name = words.next().expect("Missing output name").to_string();
+                    let vm = lookup(&ids, words.next());
+                    let last_only = match words.next() {
+                        Some("last") => true,
+                        Some("all") | None => false,
+                        Some(mode) => panic!("Unknown output mode: {}", mode),
+                    };
+                    res.outputs.push(Tap {
+                        name,
+                        vm,
+                        last_only,
+                        vals: Vec::new(),
+                    });
+                }
+                Some(word) if word.starts_with('#') => (),
+                None => (),
+                Some(_) => panic!("Invalid topology entry: {}", line),
+            }
+        }
+
+        res
+    }
+
+    /// Runs every VM in turn, passing outputs along links, until none of
+    /// them can make progress: each has halted, faulted or is waiting for
+    /// input nobody will send.
+    pub fn run(&mut self) {
+        loop {
+            let mut progress = false;
+
+            for id in 0..self.vms.len() {
+                if self.vms[id].status != Status::Running {
+                    continue;
+                }
+                run(&mut self.vms[id]);
+                progress = true;
+
+                if let Some(report) = fault_report(&self.vms[id]) {
+                    eprintln!("{} faulted: {}", self.names[id], report);
+                }
+
+                let vals: Vec<i64> = self.vms[id].output.drain(..).collect();
+                for &val in &vals {
+                    for &to in &self.links[id] {
+                        add_input(&mut self.vms[to], val);
+                    }
+                }
+                for tap in self.outputs.iter_mut().filter(|tap| tap.vm == id) {
+                    tap.vals.extend(&vals);
+                }
+            }
+
+            if !progress {
+                break;
+            }
+        }
+    }
+
+    /// The collected outputs, in the order they were declared.
+    pub fn report(&self) -> String {
+        let mut res = String::new();
+
+        for tap in &self.outputs {
+            let vals: Vec<String> = if tap.last_only {
+                tap.vals.last().iter().map(|v| v.to_string()).collect()
+            } else {
+                tap.vals.iter().map(|v| v.to_string()).collect()
+            };
+            res += &format!("{} {}\n", tap.name, vals.join(","));
+        }
+
+        res
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use num_traits::cast::FromPrimitive;
+use screen::Screen;
+
+use crate::Color;
+
+const BLACK: [u8; 3] = [0, 0, 0];
+const WHITE: [u8; 3] = [255, 255, 255];
+const TRAIL: [u8; 3] = [255, 0, 0];
+
+/// A painted hull, ready to be written out as an image. Each panel is
+/// `scale` pixels square; the robot's `trail`, if any, is drawn over the
+/// panels as a line through the middle of each one it visited.
+pub struct Painting<'a> {
+    pub surface: &'a Screen,
+    pub trail: Option<&'a [(i64, i64)]>,
+    pub scale: usize,
+}
+
+fn is_white(color: Option<i64>) -> bool {
+    matches!(color.and_then(Color::from_i64), Some(Color::White))
+}
+
+impl<'a> Painting<'a> {
+    /// Writes the image in the format named by the extension of `path`:
+    /// `.pbm`, `.png` or `.svg`.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let ext = Path::new(path).extension().and_then(|ext| ext.to_str());
+        match ext {
+            Some("pbm") => self.write_pbm(path),
+            Some("png") => self.write_png(path),
+            Some("svg") => self.write_svg(path),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown image format: {}", path),
+            )),
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.surface.width() * self.scale
+    }
+
+    fn height(&self) -> usize {
+        self.surface.height() * self.scale
+    }
+
+    /// Every pixel, row by row, as whether its panel is white and whether
+    /// the trail covers it.
+    fn raster(&self) -> Vec<(bool, bool)> {
+        let (width, height) = (self.width(), self.height());
+        let mut res = vec![(false, false); width * height];
+        let (left, top, _, _) = match self.surface.bounds() {
+            Some(bounds) => bounds,
+            None => return res,
+        };
+
+        for (i, pixel) in res.iter_mut().enumerate() {
+            let x = left + (i % width / self.scale) as i64;
+            let y = top + (i / width / self.scale) as i64;
+            pixel.0 = is_white(self.surface.get(x, y));
+        }
+
+        if let Some(trail) = self.trail {
+            let thickness = (self.scale / 4).max(1) as i64;
+            let center = |v: i64, min: i64| (v - min) * self.scale as i64 + self.scale as i64 / 2;
+
+            for step in trail.windows(2) {
+                let ((x0, y0), (x1, y1)) = (step[0], step[1]);
+                let (cx0, cx1) = (center(x0, left), center(x1, left));
+                let (cy0, cy1) = (center(y0, top), center(y1, top));
+                let from_x = (cx0.min(cx1) - thickness / 2).max(0);
+                let to_x = (cx0.max(cx1) - thickness / 2 + thickness).min(width as i64);
+                let from_y = (cy0.min(cy1) - thickness / 2).max(0);
+                let to_y = (cy0.max(cy1) - thickness / 2 + thickness).min(height as i64);
+
+                for py in from_y..to_y {
+                    for px in from_x..to_x {
+                        res[py as usize * width + px as usize].1 = true;
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// A binary PBM has no colors, so the trail inverts the panels under it.
+    fn write_pbm(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "P4\n{} {}\n", self.width(), self.height())?;
+
+        let raster = self.raster();
+        for row in raster.chunks(self.width().max(1)) {
+            // One bit per pixel, set for black, with each row padded out to
+            // a whole byte.
+            for byte in row.chunks(8) {
+                let mut bits = 0u8;
+                for (i, &(white, trail)) in byte.iter().enumerate() {
+                    if white == trail {
+                        bits |= 0x80 >> i;
+                    }
+                }
+                out.write_all(&[bits])?;
+            }
+        }
+
+        out.flush()
+    }
+
+    fn write_png(&self, path: &str) -> io::Result<()> {
+        let out = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(out, self.width() as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let data: Vec<u8> = self
+            .raster()
+            .into_iter()
+            .flat_map(|pixel| match pixel {
+                (_, true) => TRAIL,
+                (true, false) => WHITE,
+                (false, false) => BLACK,
+            })
+            .collect();
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(io::Error::other)
+    }
+
+    /// Vector output: a black background with a square per white panel,
+    /// so `scale` only sets the default size.
+    fn write_svg(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let (left, top, right, bottom) = self.surface.bounds().unwrap_or((0, 0, -1, -1));
+        let scale = self.scale as i64;
+
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+            self.width(),
+            self.height(),
+            self.surface.width(),
+            self.surface.height()
+        )?;
+        writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"black\"/>")?;
+
+        for y in top..=bottom {
+            for x in left..=right {
+                if is_white(self.surface.get(x, y)) {
+                    writeln!(
+                        out,
+                        "<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" fill=\"white\"/>",
+                        x - left,
+                        y - top
+                    )?;
+                }
+            }
+        }
+
+        if let Some(trail) = self.trail {
+            let points: Vec<String> = trail
+                .iter()
+                .map(|&(x, y)| format!("{}.5,{}.5", x - left, y - top))
+                .collect();
+            writeln!(
+                out,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"red\" stroke-width=\"{}\"/>",
+                points.join(" "),
+                ((scale / 4).max(1) as f64) / scale as f64
+            )?;
+        }
+
+        writeln!(out, "</svg>")?;
+        out.flush()
+    }
+}
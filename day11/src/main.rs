@@ -1,12 +1,22 @@
-use std::collections::{HashMap, VecDeque};
+mod export;
+mod replay;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env::args;
 use std::io::{self, BufRead};
 use std::ops::{Index, IndexMut};
 
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::cast::{FromPrimitive, ToPrimitive};
+use screen::{ocr, Screen};
+
+use export::Painting;
+use replay::{Event, Replay};
 
 const PAGE_SIZE: usize = 32768;
 const MARGIN: i64 = 1;
+const DEFAULT_SCALE: usize = 8;
+const DEFAULT_FPS: u64 = 30;
 
 #[derive(PartialEq)]
 enum Status {
@@ -219,6 +229,7 @@ fn add_input(ctx: &mut Context, val: i64) {
     }
 }
 
+#[derive(Clone, Copy)]
 enum Direction {
     Up,
     Down,
@@ -236,7 +247,12 @@ struct Robot {
     dir: Direction,
     pos_x: i64,
     pos_y: i64,
-    surface: HashMap<(i64, i64), Color>,
+    surface: Screen,
+    /// Panels painted at least once, whatever color they ended up.
+    painted: HashSet<(i64, i64)>,
+    /// Every position the robot has stood on, in order.
+    trail: Vec<(i64, i64)>,
+    events: Vec<Event>,
 }
 
 #[derive(FromPrimitive)]
@@ -246,7 +262,15 @@ enum Turn {
 }
 
 fn do_paint(robot: &mut Robot, color: Color) {
-    robot.surface.insert((robot.pos_x, robot.pos_y), color);
+    robot
+        .surface
+        .set(robot.pos_x, robot.pos_y, color.to_i64().unwrap());
+    robot.painted.insert((robot.pos_x, robot.pos_y));
+    robot.events.push(Event::Paint(
+        robot.pos_x,
+        robot.pos_y,
+        color.to_i64().unwrap(),
+    ));
 }
 
 fn do_turn(robot: &mut Robot, turn: Turn) {
@@ -260,23 +284,26 @@ fn do_turn(robot: &mut Robot, turn: Turn) {
         (Direction::Left, Turn::Left) => Direction::Down,
         (Direction::Left, Turn::Right) => Direction::Up,
     };
+    robot.events.push(Event::Turn(robot.dir));
 }
 
 fn do_move(robot: &mut Robot) {
     match &robot.dir {
         Direction::Up => {
-            robot.pos_y += 1;
+            robot.pos_y -= 1;
         }
         Direction::Right => {
             robot.pos_x += 1;
         }
         Direction::Down => {
-            robot.pos_y -= 1;
+            robot.pos_y += 1;
         }
         Direction::Left => {
             robot.pos_x -= 1;
         }
     };
+    robot.trail.push((robot.pos_x, robot.pos_y));
+    robot.events.push(Event::Move(robot.pos_x, robot.pos_y));
 }
 
 fn process_output(ctx: &mut Context, robot: &mut Robot) {
@@ -294,42 +321,73 @@ fn process_output(ctx: &mut Context, robot: &mut Robot) {
 fn read_camera(robot: &Robot) -> i64 {
     robot
         .surface
-        .get(&(robot.pos_x, robot.pos_y))
-        .unwrap_or(&Color::Black)
-        .to_i64()
-        .unwrap()
+        .get(robot.pos_x, robot.pos_y)
+        .unwrap_or_else(|| Color::Black.to_i64().unwrap())
 }
 
-fn visualize(surface: &HashMap<(i64, i64), Color>) {
-    let xs: Vec<i64> = surface.keys().map(|&(x, _)| x).collect();
-    let ys: Vec<i64> = surface.keys().map(|&(_, y)| y).collect();
+fn visualize(surface: &Screen) {
+    let mut surface = surface.clone();
+    surface.expand(MARGIN);
 
-    let top = ys.iter().max().unwrap() + MARGIN;
-    let bottom = ys.iter().min().unwrap() - MARGIN;
-    let left = xs.iter().min().unwrap() - MARGIN;
-    let right = xs.iter().max().unwrap() + MARGIN;
-
-    let num_rows = top - bottom + 1;
-    let num_cols = right - left + 1;
+    // colors inverted for readability
+    let text = surface.render_text(|color| match color.and_then(Color::from_i64) {
+        Some(Color::White) => ' ',
+        _ => '█',
+    });
+    print!("{}", text);
+}
 
-    for row in 0..num_rows {
-        let y = top - row;
-        for col in 0..num_cols {
-            let x = left + col;
+/// The white panels, row by row, for reading the registration identifier.
+fn lit_panels(surface: &Screen) -> Vec<Vec<bool>> {
+    let (left, top, right, bottom) = match surface.bounds() {
+        Some(bounds) => bounds,
+        None => return Vec::new(),
+    };
 
-            let color = surface.get(&(x,y)).unwrap_or(&Color::Black);
+    (top..=bottom)
+        .map(|y| {
+            (left..=right)
+                .map(|x| surface.get(x, y) == Color::White.to_i64())
+                .collect()
+        })
+        .collect()
+}
 
-            // colors inverted for readability
-            match color {
-                Color::Black => print!("█"),
-                Color::White => print!(" "),
+fn main() {
+    let mut start = Color::White;
+    let mut image_file = None;
+    let mut scale = DEFAULT_SCALE;
+    let mut show_trail = false;
+    let mut animate = false;
+    let mut fps = DEFAULT_FPS;
+    let mut gif_file = None;
+
+    let mut opts = args().skip(1);
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--start" => {
+                start = match opts.next().expect("Missing start color").as_str() {
+                    "black" => Color::Black,
+                    "white" => Color::White,
+                    color => panic!("Unknown color: {}", color),
+                }
             }
+            "--image" => image_file = Some(opts.next().expect("Missing image file")),
+            "--scale" => {
+                let val = opts.next().expect("Missing scale");
+                scale = val.parse::<usize>().expect("invalid scale").max(1);
+            }
+            "--trail" => show_trail = true,
+            "--animate" => animate = true,
+            "--fps" => {
+                let val = opts.next().expect("Missing frame rate");
+                fps = val.parse::<u64>().expect("invalid frame rate");
+            }
+            "--gif" => gif_file = Some(opts.next().expect("Missing GIF file")),
+            _ => panic!("Unknown option: {}", opt),
         }
-        println!();
     }
-}
 
-fn main() {
     let prog: Vec<i64> = io::stdin()
         .lock()
         .lines()
@@ -344,9 +402,12 @@ fn main() {
         dir: Direction::Up,
         pos_x: 0,
         pos_y: 0,
-        surface: HashMap::new(),
+        surface: Screen::new(),
+        painted: HashSet::new(),
+        trail: vec![(0, 0)],
+        events: Vec::new(),
     };
-    robot.surface.insert((0,0), Color::White);
+    robot.surface.set(0, 0, start.to_i64().unwrap());
 
     let mut ctx = Context {
         mem: init_vmem(&prog),
@@ -364,5 +425,45 @@ fn main() {
         }
     }
 
+    if animate || gif_file.is_some() {
+        let mut surface = robot.surface.clone();
+        surface.expand(MARGIN);
+        let replay = Replay::new(
+            start.to_i64().unwrap(),
+            &robot.events,
+            surface.bounds().unwrap(),
+        );
+
+        if animate {
+            replay.animate(fps);
+        }
+        if let Some(path) = gif_file {
+            replay
+                .write_gif(&path, scale, fps)
+                .expect("Unable to write GIF");
+        }
+    }
+
+    println!("{} panels painted", robot.painted.len());
     visualize(&robot.surface);
+
+    // Starting on a black panel paints noise rather than letters.
+    if let Color::White = start {
+        match ocr::read(&lit_panels(&robot.surface)) {
+            Ok(text) => println!("{}", text),
+            Err(e) => eprintln!("Unable to read registration: {}", e),
+        }
+    }
+
+    if let Some(path) = image_file {
+        let mut surface = robot.surface.clone();
+        surface.expand(MARGIN);
+
+        let painting = Painting {
+            surface: &surface,
+            trail: if show_trail { Some(&robot.trail) } else { None },
+            scale,
+        };
+        painting.write(&path).expect("Unable to write image");
+    }
 }
@@ -0,0 +1,206 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::thread;
+use std::time::Duration;
+
+use num_traits::cast::FromPrimitive;
+use screen::Screen;
+
+use crate::{Color, Direction};
+
+/// Most frames to put in a GIF; longer runs skip frames to fit.
+const MAX_GIF_FRAMES: usize = 500;
+
+const UNPAINTED: u8 = 0;
+const BLACK: u8 = 1;
+const WHITE: u8 = 2;
+const ROBOT: u8 = 3;
+const GIF_PALETTE: [u8; 12] = [48, 48, 48, 0, 0, 0, 255, 255, 255, 255, 0, 0];
+
+/// One thing the robot did, in the order `process_output` did it.
+pub enum Event {
+    Paint(i64, i64, i64),
+    Turn(Direction),
+    Move(i64, i64),
+}
+
+/// The hull as the robot leaves each panel, and the robot itself.
+pub struct Frame {
+    pub surface: Screen,
+    pub x: i64,
+    pub y: i64,
+    pub dir: Direction,
+}
+
+/// Plays recorded events back onto an empty hull, one frame per move.
+pub struct Replay<'a> {
+    start: i64,
+    events: &'a [Event],
+    bounds: (i64, i64, i64, i64),
+}
+
+fn heading_char(dir: Direction) -> char {
+    match dir {
+        Direction::Up => '^',
+        Direction::Right => '>',
+        Direction::Down => 'v',
+        Direction::Left => '<',
+    }
+}
+
+/// Whether the pixel at `(px, py)` of a `scale` pixel square is part of an
+/// arrowhead pointing in `dir`.
+fn in_arrow(px: usize, py: usize, scale: usize, dir: Direction) -> bool {
+    let s = scale as f64;
+    let (u, v) = ((px as f64 + 0.5) / s, (py as f64 + 0.5) / s);
+    // Turn the square so the arrow always points up.
+    let (u, v) = match dir {
+        Direction::Up => (u, v),
+        Direction::Right => (v, 1.0 - u),
+        Direction::Down => (1.0 - u, 1.0 - v),
+        Direction::Left => (1.0 - v, u),
+    };
+
+    (0.15..=0.85).contains(&v) && (u - 0.5).abs() <= (v - 0.15) * 0.5
+}
+
+impl<'a> Replay<'a> {
+    /// `start` is the color of the panel the robot starts on; every frame
+    /// covers `bounds`.
+    pub fn new(start: i64, events: &'a [Event], bounds: (i64, i64, i64, i64)) -> Replay<'a> {
+        Replay {
+            start,
+            events,
+            bounds,
+        }
+    }
+
+    fn moves(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, Event::Move(..)))
+            .count()
+    }
+
+    /// Calls `on_frame` with the starting position and then after each move.
+    pub fn play(&self, mut on_frame: impl FnMut(&Frame)) {
+        let mut frame = Frame {
+            surface: Screen::new(),
+            x: 0,
+            y: 0,
+            dir: Direction::Up,
+        };
+        frame.surface.set(0, 0, self.start);
+        frame.surface.extend_to(self.bounds);
+        on_frame(&frame);
+
+        for event in self.events {
+            match *event {
+                Event::Paint(x, y, color) => frame.surface.set(x, y, color),
+                Event::Turn(dir) => frame.dir = dir,
+                Event::Move(x, y) => {
+                    frame.x = x;
+                    frame.y = y;
+                    on_frame(&frame);
+                }
+            }
+        }
+    }
+
+    /// Animates the robot on an ANSI terminal, with the same inverted colors
+    /// as the final picture.
+    pub fn animate(&self, fps: u64) {
+        let frame_time = Duration::from_millis(1000 / fps.max(1));
+
+        print!("\x1b[2J");
+        self.play(|frame| {
+            let robot = heading_char(frame.dir);
+            let mut surface = frame.surface.clone();
+            // Anything that isn't a color marks the robot.
+            surface.set(frame.x, frame.y, -1);
+
+            surface.render_terminal(|color| match color {
+                Some(-1) => robot,
+                _ => match color.and_then(Color::from_i64) {
+                    Some(Color::White) => ' ',
+                    _ => '█',
+                },
+            });
+            thread::sleep(frame_time);
+        });
+    }
+
+    /// Writes an animated GIF, each panel `scale` pixels square and the
+    /// robot a red arrow. Long runs skip frames to keep the file small, but
+    /// the last frame is always the finished hull.
+    pub fn write_gif(&self, path: &str, scale: usize, fps: u64) -> io::Result<()> {
+        let (left, top, right, bottom) = self.bounds;
+        let width = (right - left + 1) as usize * scale;
+        let height = (bottom - top + 1) as usize * scale;
+        let every = (self.moves() + 1).div_ceil(MAX_GIF_FRAMES).max(1);
+        let delay = (100 * every as u64 / fps.max(1)).clamp(2, u16::MAX as u64) as u16;
+
+        let out = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(out, width as u16, height as u16, &GIF_PALETTE)
+            .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        let last = self.moves();
+        let mut count = 0;
+        let mut res = Ok(());
+        self.play(|frame| {
+            if (count % every == 0 || count == last) && res.is_ok() {
+                let pixels = rasterize(frame, self.bounds, scale);
+                res = write_frame(&mut encoder, width, height, &pixels, delay);
+            }
+            count += 1;
+        });
+
+        res
+    }
+}
+
+/// One palette index per pixel, row by row.
+fn rasterize(frame: &Frame, bounds: (i64, i64, i64, i64), scale: usize) -> Vec<u8> {
+    let (left, top, right, _) = bounds;
+    let width = (right - left + 1) as usize * scale;
+    let mut pixels = vec![UNPAINTED; width * frame.surface.height() * scale];
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (px, py) = (i % width, i / width);
+        let (x, y) = (left + (px / scale) as i64, top + (py / scale) as i64);
+
+        *pixel =
+            if (x, y) == (frame.x, frame.y) && in_arrow(px % scale, py % scale, scale, frame.dir) {
+                ROBOT
+            } else {
+                match frame.surface.get(x, y).and_then(Color::from_i64) {
+                    Some(Color::White) => WHITE,
+                    Some(Color::Black) => BLACK,
+                    None => UNPAINTED,
+                }
+            };
+    }
+
+    pixels
+}
+
+fn write_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    delay: u16,
+) -> io::Result<()> {
+    let frame = gif::Frame {
+        width: width as u16,
+        height: height as u16,
+        buffer: Cow::Borrowed(pixels),
+        delay,
+        ..gif::Frame::default()
+    };
+    encoder.write_frame(&frame).map_err(io::Error::other)
+}
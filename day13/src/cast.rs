@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use crate::{tile_char, Game, HEIGHT, WIDTH};
+
+/// Records frames as an asciicast v2 file, for playback with `asciinema
+/// play` or any other asciicast player.
+pub struct Cast {
+    out: BufWriter<File>,
+    start: Instant,
+    /// Time between frames when the game isn't running in real time.
+    /// Without one, frames are stamped with the wall clock.
+    frame_time: Option<Duration>,
+    frames: u32,
+}
+
+fn escape(text: &str) -> String {
+    let mut res = String::new();
+
+    for c in text.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\r\\n",
+            c if (c as u32) < 0x20 => res += &format!("\\u{:04x}", c as u32),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+impl Cast {
+    pub fn create(path: &str, frame_time: Option<Duration>) -> Cast {
+        let mut out = BufWriter::new(File::create(path).expect("Unable to create cast file"));
+        writeln!(
+            out,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}}}",
+            WIDTH,
+            HEIGHT + 1
+        )
+        .expect("Unable to write cast");
+
+        Cast {
+            out,
+            start: Instant::now(),
+            frame_time,
+            frames: 0,
+        }
+    }
+
+    pub fn frame(&mut self, game: &Game) {
+        let time = match self.frame_time {
+            Some(frame_time) => frame_time * self.frames,
+            None => self.start.elapsed(),
+        };
+        let clear = if self.frames == 0 { "\x1b[2J" } else { "" };
+        let text = format!(
+            "{}\x1b[H{}{}\x1b[K",
+            clear,
+            game.screen.render_text(tile_char),
+            game.score
+        );
+
+        writeln!(
+            self.out,
+            "[{:.6}, \"o\", \"{}\"]",
+            time.as_secs_f64(),
+            escape(&text)
+        )
+        .expect("Unable to write cast");
+        self.frames += 1;
+    }
+}
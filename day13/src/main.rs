@@ -1,14 +1,26 @@
+mod cast;
+mod plan;
+mod play;
+mod session;
+
 use std::collections::{HashMap, VecDeque};
 use std::env::args;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::ops::{Index, IndexMut};
+use std::sync::Arc;
 use std::thread;
 use std::time;
 
 use ncurses::*;
 use num_derive::FromPrimitive;
 use num_traits::cast::FromPrimitive;
+use screen::{Screen, TileDecoder};
+
+use cast::Cast;
+use plan::Planner;
+use play::Play;
+use session::{Event, Session};
 
 const PAGE_SIZE: usize = 32768;
 const WIDTH: i32 = 42;
@@ -16,7 +28,7 @@ const HEIGHT: i32 = 24;
 
 const SLEEP_TIME: time::Duration = time::Duration::from_millis(50);
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 enum Status {
     Running,
     WaitingForInput,
@@ -24,8 +36,11 @@ enum Status {
 }
 
 type Page = [i64; PAGE_SIZE];
+
+/// Pages are shared between clones and copied on their first write.
+#[derive(Clone)]
 struct Vmem {
-    table: HashMap<usize, Page>,
+    table: HashMap<usize, Arc<Page>>,
 }
 
 impl Index<usize> for Vmem {
@@ -44,7 +59,12 @@ impl IndexMut<usize> for Vmem {
         let page_num = idx / PAGE_SIZE;
         let page_off = idx % PAGE_SIZE;
 
-        &mut self.table.entry(page_num).or_insert([0; PAGE_SIZE])[page_off]
+        let page = self
+            .table
+            .entry(page_num)
+            .or_insert_with(|| Arc::new([0; PAGE_SIZE]));
+
+        &mut Arc::make_mut(page)[page_off]
     }
 }
 
@@ -60,6 +80,7 @@ fn init_vmem(prog: &[i64]) -> Vmem {
     res
 }
 
+#[derive(Clone)]
 struct Context {
     mem: Vmem,
     pc: usize,
@@ -67,6 +88,23 @@ struct Context {
     input: VecDeque<i64>,
     output: VecDeque<i64>,
     rel_base: i64,
+    session: Option<Session>,
+}
+
+impl Context {
+    /// Cheap copy of the VM: memory pages stay shared until either side
+    /// writes to them. The recorded session stays with the original.
+    fn fork(&self) -> Context {
+        Context {
+            mem: self.mem.clone(),
+            pc: self.pc,
+            status: self.status.clone(),
+            input: self.input.clone(),
+            output: self.output.clone(),
+            rel_base: self.rel_base,
+            session: None,
+        }
+    }
 }
 
 fn get_arg(ctx: &Context, offset: usize) -> i64 {
@@ -127,6 +165,9 @@ fn input(ctx: &mut Context) {
     if let Some(val) = ctx.input.pop_front() {
         let out = get_out(ctx, 1);
         *out = val;
+        if let Some(session) = &mut ctx.session {
+            session.events.push(Event::Input(val));
+        }
         ctx.pc += 2;
     } else {
         ctx.status = Status::WaitingForInput;
@@ -137,6 +178,9 @@ fn output(ctx: &mut Context) {
     let val = get_arg(ctx, 1);
 
     ctx.output.push_back(val);
+    if let Some(session) = &mut ctx.session {
+        session.events.push(Event::Output(val));
+    }
     ctx.pc += 2;
 }
 
@@ -236,51 +280,67 @@ enum Tile {
     Ball = 4,
 }
 
+#[derive(Clone)]
 struct Game {
-    window: WINDOW,
+    /// The ncurses window to draw on, if any. Without one the game runs
+    /// headless at full speed.
+    window: Option<WINDOW>,
     joystick_state: i64,
+    screen: Screen,
+    decoder: TileDecoder,
+    score: i64,
     pad_x: i64,
-    ball_x: i64
+    pad_y: i64,
+    ball_x: i64,
+    ball_y: i64,
+}
+
+fn tile_char(tile: Option<i64>) -> char {
+    match tile.and_then(Tile::from_i64) {
+        Some(Tile::Wall) => 'X',
+        Some(Tile::Block) => '=',
+        Some(Tile::Paddle) => '-',
+        Some(Tile::Ball) => 'o',
+        Some(Tile::Empty) | None => ' ',
+    }
 }
 
 fn process_output(ctx: &mut Context, game: &mut Game) {
-    while let Some(x) = ctx.output.pop_front() {
-        let y = ctx.output.pop_front().unwrap();
-
-        if x != -1 {
-            let tile = Tile::from_i64(ctx.output.pop_front().unwrap()).unwrap();
-            wmove(game.window, y as i32, x as i32);
-            let ch = match tile {
-                Tile::Empty => ' ' as chtype,
-                Tile::Wall => 'X' as chtype,
-                Tile::Block => '=' as chtype,
-                Tile::Paddle => { game.pad_x = x; '-' as chtype }
-                Tile::Ball => { game.ball_x = x; 'o' as chtype }
-            };
+    while let Some(val) = ctx.output.pop_front() {
+        if let Some((_, _, score)) = game.decoder.feed(&mut game.screen, val) {
+            game.score = score;
+        }
+    }
 
-            waddch(game.window, ch);
-        } else {
-            let score = ctx.output.pop_front().unwrap().to_string();
-            wmove(game.window, HEIGHT, 0);
-            waddstr(game.window, &score);
+    for ((x, y), tile) in game.screen.cells() {
+        match Tile::from_i64(tile) {
+            Some(Tile::Paddle) => {
+                game.pad_x = x;
+                game.pad_y = y;
+            }
+            Some(Tile::Ball) => {
+                game.ball_x = x;
+                game.ball_y = y;
+            }
+            _ => (),
         }
     }
-    wrefresh(game.window);
+
+    draw(game);
 }
 
-fn get_input(game: &mut Game) -> i64 {
-    /*
-    let ch = getch() as u8 as char;
-
-    match ch {
-        'a' => game.joystick_state = -1,
-        's' => game.joystick_state = 0,
-        'd' => game.joystick_state = 1,
-        _ => ()
-    };
+fn draw(game: &Game) {
+    if let Some(window) = game.window {
+        for (y, line) in game.screen.render_text(tile_char).lines().enumerate() {
+            mvaddstr(y as i32, 0, line);
+        }
+        mvaddstr(HEIGHT, 0, &game.score.to_string());
+        wclrtoeol(window);
+        wrefresh(window);
+    }
+}
 
-    game.joystick_state
-    */
+fn get_input(game: &mut Game) -> i64 {
     if game.ball_x > game.pad_x {
         1
     } else if game.ball_x < game.pad_x {
@@ -290,8 +350,48 @@ fn get_input(game: &mut Game) -> i64 {
     }
 }
 
+fn new_game(window: Option<WINDOW>) -> Game {
+    Game {
+        window,
+        joystick_state: 0,
+        screen: Screen::new(),
+        decoder: TileDecoder::default(),
+        score: 0,
+        pad_x: 0,
+        pad_y: 0,
+        ball_x: 0,
+        ball_y: 0,
+    }
+}
+
 fn main() {
     let prog_file = File::open(args().nth(1).expect("No program!")).unwrap();
+    let mut session_file = None;
+    let mut use_ncurses = false;
+    let mut play = None;
+    let mut planner = None;
+    let mut cast_file = None;
+
+    let mut opts = args().skip(2);
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--record" => session_file = Some(opts.next().expect("Missing session file")),
+            "--ncurses" => use_ncurses = true,
+            "--plan" => planner = Some(Planner::default()),
+            "--cast" => cast_file = Some(opts.next().expect("Missing cast file")),
+            "--play" => {
+                use_ncurses = true;
+                play = Some(Play::new(1000 / SLEEP_TIME.as_millis() as u64));
+            }
+            "--fps" => {
+                let fps = opts.next().expect("Missing frame rate");
+                play = Some(Play::new(fps.parse::<u64>().expect("invalid frame rate")));
+                use_ncurses = true;
+            }
+            _ => panic!("Unknown option: {}", opt),
+        }
+    }
+
     let mut prog: Vec<i64> = BufReader::new(prog_file)
         .lines()
         .next()
@@ -301,19 +401,33 @@ fn main() {
         .map(|w| w.parse::<i64>().expect("invalid int"))
         .collect();
 
-    initscr();
-    noecho();
-    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    // Without quarters the cabinet draws the first frame and halts.
+    let mut first_frame = new_game(None);
+    let mut ctx = Context {
+        mem: init_vmem(&prog),
+        pc: 0,
+        status: Status::Running,
+        input: VecDeque::new(),
+        output: VecDeque::new(),
+        rel_base: 0,
+        session: None,
+    };
+    run(&mut ctx);
+    process_output(&mut ctx, &mut first_frame);
+    let num_blocks = first_frame.screen.count(Tile::Block as i64);
+
+    let window = if use_ncurses {
+        initscr();
+        noecho();
+        curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        Some(stdscr())
+    } else {
+        None
+    };
 
     prog[0] = 2;
 
-    let mut game = Game {
-        window: stdscr(),
-        joystick_state: 0,
-        pad_x: 0,
-        ball_x: 0
-    };
-    //nodelay(game.window, true);
+    let mut game = new_game(window);
 
     let mut ctx = Context {
         mem: init_vmem(&prog),
@@ -322,16 +436,52 @@ fn main() {
         input: VecDeque::new(),
         output: VecDeque::new(),
         rel_base: 0,
+        session: session_file.as_ref().map(|_| Session::new(&prog)),
     };
-    while ctx.status != Status::Halted {
+    // Headless games run flat out, so stamp their frames as if they ran at
+    // the usual speed.
+    let frame_time = if use_ncurses { None } else { Some(SLEEP_TIME) };
+    let mut cast = cast_file.map(|path| Cast::create(&path, frame_time));
+
+    if let Some(play) = &mut play {
+        play.run(&mut ctx, &mut game, cast.as_mut());
+    }
+    while ctx.status != Status::Halted && play.is_none() {
         run(&mut ctx);
         process_output(&mut ctx, &mut game);
+        if let Some(cast) = &mut cast {
+            cast.frame(&game);
+        }
         if ctx.status == Status::WaitingForInput {
-            add_input(&mut ctx, get_input(&mut game));
+            let input = match &mut planner {
+                Some(planner) => planner.next_input(&ctx, &game),
+                None => get_input(&mut game),
+            };
+            add_input(&mut ctx, input);
         }
-        thread::sleep(SLEEP_TIME);
+        if game.window.is_some() {
+            thread::sleep(SLEEP_TIME);
+        }
+    }
+    if game.window.is_some() {
+        if play.is_none() {
+            thread::sleep(1000 * SLEEP_TIME);
+        }
+        endwin();
     }
-    thread::sleep(1000 * SLEEP_TIME);
 
-    endwin();
+    println!("{}", num_blocks);
+    println!("{}", game.score);
+    if let Some(planner) = planner {
+        println!(
+            "{} frames, {} moves, {} blocks left",
+            planner.frames,
+            planner.moves,
+            game.screen.count(Tile::Block as i64)
+        );
+    }
+
+    if let (Some(path), Some(session)) = (session_file, &ctx.session) {
+        session.save(&path);
+    }
 }
@@ -0,0 +1,57 @@
+use crate::{add_input, process_output, run, Context, Game, Status};
+
+/// Autopilot that looks ahead instead of chasing the ball. Each time the
+/// ball leaves the paddle it simulates a copy of the cabinet, with the
+/// joystick held still, until the ball comes back down to the paddle's row,
+/// then moves the paddle straight to that column and waits there.
+#[derive(Default)]
+pub struct Planner {
+    target: Option<i64>,
+    pub frames: usize,
+    pub moves: usize,
+}
+
+/// Where the ball will be when it next reaches the row above the paddle,
+/// or `None` if the game ends first.
+fn predict_landing(ctx: &Context, game: &Game) -> Option<i64> {
+    let mut ctx = ctx.fork();
+    let mut game = game.clone();
+    game.window = None;
+
+    loop {
+        add_input(&mut ctx, 0);
+        run(&mut ctx);
+        process_output(&mut ctx, &mut game);
+
+        if ctx.status == Status::Halted {
+            return None;
+        }
+        if game.ball_y == game.pad_y - 1 {
+            return Some(game.ball_x);
+        }
+    }
+}
+
+impl Planner {
+    /// Joystick position for the frame `ctx` is waiting on.
+    pub fn next_input(&mut self, ctx: &Context, game: &Game) -> i64 {
+        // On the landing frame the paddle has to stay under the ball, so
+        // look for the next landing once it is on its way back up.
+        let target = if game.ball_y == game.pad_y - 1 {
+            self.target = None;
+            game.ball_x
+        } else {
+            if self.target.is_none() {
+                self.target = predict_landing(ctx, game);
+            }
+            self.target.unwrap_or(game.pad_x)
+        };
+        let input = (target - game.pad_x).signum();
+
+        self.frames += 1;
+        if input != 0 {
+            self.moves += 1;
+        }
+        input
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+use ncurses::*;
+
+use crate::cast::Cast;
+use crate::{add_input, draw, process_output, run, Context, Game, Status, Tile, HEIGHT};
+
+/// How far `r` goes back.
+const REWIND_SECONDS: u64 = 3;
+const MIN_FPS: u64 = 1;
+const MAX_FPS: u64 = 60;
+
+const HELP: &str = "a/d or arrows move, p pause, +/- speed, k save, l load, r rewind, q quit";
+
+/// A forked VM, the game, and how many events had been recorded by then.
+type State = (Context, Game, usize);
+
+fn recorded(ctx: &Context) -> usize {
+    ctx.session
+        .as_ref()
+        .map_or(0, |session| session.events.len())
+}
+
+/// An interactive game: the joystick follows the keyboard, one frame per
+/// input the cabinet asks for.
+pub struct Play {
+    fps: u64,
+    paused: bool,
+    quick_save: Option<State>,
+    /// The most recent frames, oldest first, for rewinding.
+    history: VecDeque<State>,
+}
+
+enum Action {
+    Continue,
+    Restore(Box<State>),
+    Quit,
+}
+
+impl Play {
+    pub fn new(fps: u64) -> Play {
+        Play {
+            fps: fps.clamp(MIN_FPS, MAX_FPS),
+            paused: false,
+            quick_save: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn frame_time(&self) -> Duration {
+        Duration::from_millis(1000 / self.fps)
+    }
+
+    fn status(&self, game: &Game, msg: &str) {
+        let line = format!("{}  {} fps  {}", game.score, self.fps, msg);
+        mvaddstr(HEIGHT, 0, &line);
+        clrtoeol();
+        mvaddstr(HEIGHT + 1, 0, HELP);
+        refresh();
+    }
+
+    fn rewind(&mut self) -> Option<State> {
+        let frames = (REWIND_SECONDS * self.fps) as usize;
+        let keep = self.history.len().saturating_sub(frames).max(1);
+        self.history.truncate(keep);
+        self.history.back().cloned()
+    }
+
+    /// Handles one key press, updating the joystick in `game`.
+    fn key(&mut self, ch: i32, game: &mut Game) -> Action {
+        match ch {
+            KEY_LEFT => game.joystick_state = -1,
+            KEY_RIGHT => game.joystick_state = 1,
+            _ if ch == KEY_F5 => return self.key('k' as i32, game),
+            _ if ch == KEY_F9 => return self.key('l' as i32, game),
+            _ => match ch as u8 as char {
+                'a' => game.joystick_state = -1,
+                'd' => game.joystick_state = 1,
+                's' | ' ' => game.joystick_state = 0,
+                'p' => {
+                    self.paused = !self.paused;
+                    self.status(game, if self.paused { "paused" } else { "" });
+                }
+                '+' | '=' => self.fps = (self.fps * 2).min(MAX_FPS),
+                '-' => self.fps = (self.fps / 2).max(MIN_FPS),
+                'k' => self.quick_save = self.history.back().cloned(),
+                'l' => {
+                    if let Some(state) = &self.quick_save {
+                        return Action::Restore(Box::new(state.clone()));
+                    }
+                }
+                'r' => {
+                    if let Some(state) = self.rewind() {
+                        return Action::Restore(Box::new(state));
+                    }
+                }
+                'q' => return Action::Quit,
+                _ => (),
+            },
+        }
+
+        Action::Continue
+    }
+
+    /// Reads the keys pressed since the last frame, or waits for them while
+    /// paused.
+    fn poll(&mut self, game: &mut Game) -> Action {
+        game.joystick_state = 0;
+
+        loop {
+            nodelay(stdscr(), !self.paused);
+
+            let ch = getch();
+            if ch == ERR {
+                return Action::Continue;
+            }
+            match self.key(ch, game) {
+                Action::Continue => (),
+                action => return action,
+            }
+        }
+    }
+
+    /// Plays until the game is won or the player quits. A lost ball pauses
+    /// the game so the player can rewind or load.
+    pub fn run(&mut self, ctx: &mut Context, game: &mut Game, mut cast: Option<&mut Cast>) {
+        keypad(stdscr(), true);
+
+        loop {
+            run(ctx);
+            process_output(ctx, game);
+            if let Some(cast) = &mut cast {
+                cast.frame(game);
+            }
+
+            if ctx.status == Status::Halted {
+                if game.screen.count(Tile::Block as i64) == 0 {
+                    self.status(game, "you win!");
+                    thread::sleep(Duration::from_secs(3));
+                    return;
+                }
+                self.paused = true;
+                self.status(game, "ball lost; r to rewind, l to load, q to quit");
+            } else {
+                self.history
+                    .push_back((ctx.fork(), game.clone(), recorded(ctx)));
+                let limit = (REWIND_SECONDS * self.fps) as usize + 1;
+                while self.history.len() > limit {
+                    self.history.pop_front();
+                }
+                self.status(game, "");
+            }
+
+            match self.poll(game) {
+                Action::Continue => {
+                    if ctx.status == Status::Halted {
+                        continue;
+                    }
+                    add_input(ctx, game.joystick_state);
+                }
+                Action::Restore(state) => {
+                    let (saved_ctx, saved_game, recorded) = *state;
+                    // Keep recording, minus the frames being undone.
+                    let mut session = ctx.session.take();
+                    if let Some(session) = &mut session {
+                        session.events.truncate(recorded);
+                    }
+                    *ctx = saved_ctx;
+                    ctx.session = session;
+                    *game = saved_game;
+                    self.paused = false;
+                    draw(game);
+                    if let Some(cast) = &mut cast {
+                        cast.frame(game);
+                    }
+                    add_input(ctx, 0);
+                }
+                Action::Quit => return,
+            }
+
+            thread::sleep(self.frame_time());
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::fs;
+
+#[derive(Clone)]
+pub enum Event {
+    Input(i64),
+    Output(i64),
+}
+
+/// A recorded run in the session format replayed by day09's `--replay`.
+#[derive(Clone)]
+pub struct Session {
+    pub prog: Vec<i64>,
+    pub events: Vec<Event>,
+}
+
+impl Session {
+    pub fn new(prog: &[i64]) -> Session {
+        Session {
+            prog: prog.to_vec(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let mut contents = String::from("# intcode session\n");
+
+        let prog: Vec<String> = self.prog.iter().map(|v| v.to_string()).collect();
+        contents += &format!("program {}\n", prog.join(","));
+        for event in &self.events {
+            contents += &match event {
+                Event::Input(val) => format!("input {}\n", val),
+                Event::Output(val) => format!("output {}\n", val),
+            };
+        }
+
+        fs::write(path, contents).expect("Unable to write session");
+    }
+}
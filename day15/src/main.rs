@@ -1,3 +1,5 @@
+mod map;
+
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env::args;
 use std::fs::File;
@@ -8,6 +10,8 @@ use num_derive::ToPrimitive;
 use num_traits::cast::ToPrimitive;
 
 const PAGE_SIZE: usize = 32768;
+const MAP_SCALE: usize = 8;
+const DEFAULT_FPS: u64 = 10;
 
 #[derive(PartialEq)]
 enum Status {
@@ -315,30 +319,30 @@ fn plan_path(droid: &Droid, tgt: (i64, i64)) -> VecDeque<Direction> {
     result
 }
 
-fn time_floodfill(droid: &mut Droid) -> usize {
+/// The minute at which oxygen reaches each open cell, spreading from the
+/// oxygen system. This is also each cell's distance from the system.
+fn floodfill(droid: &Droid) -> HashMap<(i64, i64), usize> {
     let mut filled: HashMap<(i64, i64), usize> = HashMap::new();
     let mut to_prop: VecDeque<(i64, i64)> = VecDeque::new();
 
     filled.insert(droid.oxy.unwrap(), 0);
     to_prop.push_back(droid.oxy.unwrap());
 
-    let mut max: usize = 0;
-
     while let Some(pos) = to_prop.pop_front() {
         let next_val = filled[&pos] + 1;
         for next in explore(pos) {
             if droid.reachable[&next] && !filled.contains_key(&next) {
-                max = next_val;
                 filled.insert(next, next_val);
                 to_prop.push_back(next);
             }
         }
     }
 
-    max
+    filled
 }
 
-fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
+/// Returns true once every reachable cell has been explored.
+fn process_output(ctx: &mut Context, droid: &mut Droid) -> bool {
     while droid.path.len() > 1 {
         let val = ctx.output.pop_front().unwrap();
         assert!(val != 0);
@@ -353,16 +357,16 @@ fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
             droid.reachable.insert(pos, false);
 
             if droid.to_explore.is_empty() {
-                return Some(time_floodfill(droid));
+                return true;
             }
 
-            let next = droid.to_explore.pop_back().unwrap();;
+            let next = droid.to_explore.pop_back().unwrap();
             droid.path = plan_path(droid, next);
             for dir in &droid.path {
                 add_input(ctx, dir.to_i64().unwrap());
             }
 
-            None
+            false
         }
         1 => {
             droid.cur = pos;
@@ -376,7 +380,7 @@ fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
             }
 
             if droid.to_explore.is_empty() {
-                return Some(time_floodfill(droid));
+                return true;
             }
 
             let next = droid.to_explore.pop_back().unwrap();
@@ -385,15 +389,13 @@ fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
                 add_input(ctx, dir.to_i64().unwrap());
             }
 
-            None
+            false
         }
         2 => {
             droid.cur = do_move(droid.cur, last_move);
             droid.reachable.insert(droid.cur, true);
             droid.oxy = Some(droid.cur);
 
-            // part 1
-            // Some(plan_path(droid, (0, 0)).len())
             for next in explore(pos) {
                 if !droid.seen.contains(&next) {
                     droid.seen.insert(next);
@@ -402,7 +404,7 @@ fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
             }
 
             if droid.to_explore.is_empty() {
-                return Some(time_floodfill(droid));
+                return true;
             }
 
             let next = droid.to_explore.pop_back().unwrap();
@@ -411,7 +413,7 @@ fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
                 add_input(ctx, dir.to_i64().unwrap());
             }
 
-            None
+            false
         }
         _ => unreachable!(),
     }
@@ -419,6 +421,28 @@ fn process_output(ctx: &mut Context, droid: &mut Droid) -> Option<usize> {
 
 fn main() {
     let prog_file = File::open(args().nth(1).expect("No program!")).unwrap();
+    let mut map_file = None;
+    let mut scale = MAP_SCALE;
+    let mut animate = false;
+    let mut fps = DEFAULT_FPS;
+
+    let mut opts = args().skip(2);
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--map" => map_file = Some(opts.next().expect("Missing map file")),
+            "--scale" => {
+                let val = opts.next().expect("Missing scale");
+                scale = val.parse::<usize>().expect("invalid scale").max(1);
+            }
+            "--animate" => animate = true,
+            "--fps" => {
+                let val = opts.next().expect("Missing frame rate");
+                fps = val.parse::<u64>().expect("invalid frame rate");
+            }
+            _ => panic!("Unknown option: {}", opt),
+        }
+    }
+
     let prog: Vec<i64> = BufReader::new(prog_file)
         .lines()
         .next()
@@ -462,9 +486,22 @@ fn main() {
     add_input(&mut ctx, Direction::North.to_i64().unwrap());
     while ctx.status != Status::Halted {
         run(&mut ctx);
-        if let Some(dist) = process_output(&mut ctx, &mut droid) {
-            println!("{}", dist);
-            return;
+        if process_output(&mut ctx, &mut droid) {
+            break;
         }
     }
+
+    let filled = floodfill(&droid);
+    let map = map::build(&droid);
+    if animate {
+        map::animate_flood(&map, &filled, fps);
+    }
+    if let Some(path) = map_file {
+        map::write(&map, &path, scale).expect("Unable to write map");
+    }
+
+    // part 1
+    println!("{}", filled[&(0, 0)]);
+    // part 2
+    println!("{}", filled.values().max().unwrap());
 }
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use screen::Screen;
+
+use crate::Droid;
+
+// The first three match the droid's status codes.
+const WALL: i64 = 0;
+const OPEN: i64 = 1;
+const OXYGEN_SYSTEM: i64 = 2;
+const START: i64 = 3;
+const OXYGEN: i64 = 4;
+
+fn tile_char(tile: Option<i64>) -> char {
+    match tile {
+        Some(WALL) => '#',
+        Some(OPEN) => '.',
+        Some(OXYGEN_SYSTEM) => 'O',
+        Some(START) => 'S',
+        Some(OXYGEN) => 'o',
+        _ => ' ',
+    }
+}
+
+fn tile_color(tile: Option<i64>) -> [u8; 3] {
+    match tile {
+        Some(WALL) => [64, 64, 64],
+        Some(OPEN) => [224, 224, 224],
+        Some(OXYGEN_SYSTEM) => [0, 96, 255],
+        Some(START) => [0, 192, 0],
+        Some(OXYGEN) => [128, 192, 255],
+        _ => [0, 0, 0],
+    }
+}
+
+/// Everything the droid found: walls, open cells, where it started and the
+/// oxygen system. Cells it never saw are left blank.
+pub fn build(droid: &Droid) -> Screen {
+    let mut res = Screen::new();
+
+    for (&(x, y), &reachable) in &droid.reachable {
+        res.set(x, y, if reachable { OPEN } else { WALL });
+    }
+    res.set(0, 0, START);
+    if let Some((x, y)) = droid.oxy {
+        res.set(x, y, OXYGEN_SYSTEM);
+    }
+
+    res
+}
+
+/// Writes the map as a PPM image, `scale` pixels per cell, if `path` ends
+/// in `.ppm`, and as text otherwise.
+pub fn write(map: &Screen, path: &str, scale: usize) -> io::Result<()> {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("ppm") => map.write_ppm(path, scale, tile_color),
+        _ => std::fs::write(path, map.render_text(tile_char)),
+    }
+}
+
+/// Shows oxygen spreading through the map on an ANSI terminal, one frame
+/// per minute. `filled` holds the minute each cell fills.
+pub fn animate_flood(map: &Screen, filled: &HashMap<(i64, i64), usize>, fps: u64) {
+    let frame_time = Duration::from_millis(1000 / fps.max(1));
+    let minutes = filled.values().cloned().max().unwrap_or(0);
+    let mut frame = map.clone();
+
+    print!("\x1b[2J");
+    for minute in 0..=minutes {
+        for (&(x, y), _) in filled.iter().filter(|&(_, &m)| m == minute) {
+            if frame.get(x, y) == Some(OPEN) {
+                frame.set(x, y, OXYGEN);
+            }
+        }
+
+        frame.render_terminal(tile_char);
+        println!("minute {}", minute);
+        thread::sleep(frame_time);
+    }
+}
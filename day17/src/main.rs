@@ -4,6 +4,8 @@ use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::ops::{Index, IndexMut};
 
+use screen::{AsciiDecoder, Screen};
+
 const PAGE_SIZE: usize = 32768;
 
 #[derive(PartialEq)]
@@ -238,26 +240,24 @@ struct Inst {
 }
 
 fn parse_img(ctx: &mut Context) -> Vec<Vec<i64>> {
-    let mut img: Vec<Vec<i64>> = Vec::new();
-    let mut row: Vec<i64> = Vec::new();
+    let mut screen = Screen::new();
+    let mut decoder = AsciiDecoder::default();
 
     while let Some(val) = ctx.output.pop_front() {
-        match val {
-            10 => {
-                img.push(row);
-                row = Vec::new();
-            }
-            _ => row.push(val),
-        };
+        assert!(decoder.feed(&mut screen, val).is_none());
     }
 
-    assert!(row.is_empty());
-
-    img
+    (0..screen.height() as i64)
+        .map(|y| {
+            (0..screen.width() as i64)
+                .map(|x| screen.get(x, y).unwrap())
+                .collect()
+        })
+        .collect()
 }
 
 fn alignment(img: &[Vec<i64>]) -> usize {
-    let num_rows = img.len() - 1;
+    let num_rows = img.len();
     let num_cols = img[0].len();
 
     let mut sum = 0;
@@ -295,7 +295,7 @@ fn get_robot_pos(img: &[Vec<i64>]) -> (usize, usize, Direction) {
 }
 
 fn try_move(robot: &(usize, usize, Direction), img: &[Vec<i64>]) -> Option<(usize, usize)> {
-    let num_rows = img.len() - 1;
+    let num_rows = img.len();
     let num_cols = img[0].len();
 
     match robot.2 {
@@ -331,7 +331,7 @@ fn try_move(robot: &(usize, usize, Direction), img: &[Vec<i64>]) -> Option<(usiz
 }
 
 fn construct_path(img: &[Vec<i64>]) -> Vec<Inst> {
-    let num_rows = img.len() - 1;
+    let num_rows = img.len();
     let num_cols = img[0].len();
 
     let mut path: Vec<Inst> = Vec::new();
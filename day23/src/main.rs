@@ -0,0 +1,136 @@
+mod network;
+
+use std::env::args;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process;
+
+use network::{Network, Packet, QuietTurns, Router};
+
+const NUM_NICS: usize = 50;
+const NAT_ADDR: i64 = 255;
+/// Turns each NIC must spend without traffic before the network counts as
+/// idle. NICs can take a couple of empty reads before they settle.
+const IDLE_TURNS: usize = 2;
+
+/// Part 1: stops at the first packet sent to the NAT.
+#[derive(Default)]
+struct FirstToNat {
+    y: Option<i64>,
+}
+
+impl Router for FirstToNat {
+    fn intercept(&mut self, packet: &Packet) -> bool {
+        if packet.dest != NAT_ADDR {
+            return false;
+        }
+        if self.y.is_none() {
+            self.y = Some(packet.y);
+        }
+        true
+    }
+
+    fn on_idle(&mut self) -> Vec<Packet> {
+        Vec::new()
+    }
+
+    fn answer(&self) -> Option<i64> {
+        self.y
+    }
+}
+
+/// Part 2: keeps the last packet sent to the NAT and sends it to NIC 0
+/// whenever the network is idle, stopping once it sends the same y twice in
+/// a row.
+#[derive(Default)]
+struct Nat {
+    last: Option<(i64, i64)>,
+    sent_y: Option<i64>,
+    answer: Option<i64>,
+}
+
+impl Router for Nat {
+    fn intercept(&mut self, packet: &Packet) -> bool {
+        if packet.dest != NAT_ADDR {
+            return false;
+        }
+        self.last = Some((packet.x, packet.y));
+        true
+    }
+
+    fn on_idle(&mut self) -> Vec<Packet> {
+        let (x, y) = match self.last {
+            Some(last) => last,
+            None => return Vec::new(),
+        };
+
+        if self.sent_y == Some(y) {
+            self.answer = Some(y);
+        }
+        self.sent_y = Some(y);
+
+        vec![Packet {
+            src: NAT_ADDR as usize,
+            dest: 0,
+            x,
+            y,
+        }]
+    }
+
+    fn answer(&self) -> Option<i64> {
+        self.answer
+    }
+}
+
+fn simulate(prog: &[i64], router: &mut dyn Router, trace: Option<&mut dyn Write>) -> i64 {
+    let mut idle = QuietTurns::new(NUM_NICS, IDLE_TURNS);
+    let mut network = Network::new(prog, NUM_NICS, router, &mut idle);
+    if let Some(out) = trace {
+        network.trace(out);
+    }
+
+    network.run().unwrap_or_else(|e| {
+        eprintln!("Network stopped: {}", e);
+        process::exit(1);
+    })
+}
+
+fn main() {
+    let prog_file = File::open(args().nth(1).expect("No program!")).unwrap();
+    let prog: Vec<i64> = BufReader::new(prog_file)
+        .lines()
+        .next()
+        .expect("No input")
+        .unwrap()
+        .split(',')
+        .map(|w| w.parse::<i64>().expect("invalid int"))
+        .collect();
+
+    let mut trace = match (args().nth(2).as_deref(), args().nth(3)) {
+        (Some("--trace"), Some(path)) => Some(BufWriter::new(
+            File::create(path).expect("Unable to create trace file"),
+        )),
+        (None, _) => None,
+        _ => panic!("Usage: day23 PROGRAM [--trace FILE]"),
+    };
+
+    if let Some(out) = &mut trace {
+        writeln!(out, "# part 1").unwrap();
+    }
+    let part1 = simulate(
+        &prog,
+        &mut FirstToNat::default(),
+        trace.as_mut().map(|out| out as &mut dyn Write),
+    );
+    println!("{}", part1);
+
+    if let Some(out) = &mut trace {
+        writeln!(out, "# part 2").unwrap();
+    }
+    let part2 = simulate(
+        &prog,
+        &mut Nat::default(),
+        trace.as_mut().map(|out| out as &mut dyn Write),
+    );
+    println!("{}", part2);
+}
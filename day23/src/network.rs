@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::io::Write;
+
+use intcode::{add_input, fault_report, run, Context, Status};
+
+#[derive(Clone, Copy)]
+pub struct Packet {
+    pub src: usize,
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Decides what happens to packets beyond plain delivery between NICs.
+pub trait Router {
+    /// Offered every packet sent. Returns true if the router consumed it;
+    /// otherwise it is delivered to its destination NIC, if there is one.
+    fn intercept(&mut self, packet: &Packet) -> bool;
+
+    /// Called whenever the network is idle. Returned packets are delivered.
+    fn on_idle(&mut self) -> Vec<Packet>;
+
+    /// The result of the simulation, once there is one.
+    fn answer(&self) -> Option<i64>;
+}
+
+/// Decides when the network has gone idle.
+pub trait IdleDetector {
+    /// Called after each turn a NIC gets, with whether it received or sent
+    /// any packets during it.
+    fn record(&mut self, addr: usize, received: bool, sent: bool);
+
+    fn is_idle(&self, queues_empty: bool) -> bool;
+}
+
+/// Idle once every queue is empty and every NIC has gone `threshold` turns
+/// without receiving or sending.
+pub struct QuietTurns {
+    threshold: usize,
+    quiet: Vec<usize>,
+}
+
+impl QuietTurns {
+    pub fn new(size: usize, threshold: usize) -> QuietTurns {
+        QuietTurns {
+            threshold,
+            quiet: vec![0; size],
+        }
+    }
+}
+
+impl IdleDetector for QuietTurns {
+    fn record(&mut self, addr: usize, received: bool, sent: bool) {
+        if received || sent {
+            self.quiet[addr] = 0;
+        } else {
+            self.quiet[addr] += 1;
+        }
+    }
+
+    fn is_idle(&self, queues_empty: bool) -> bool {
+        queues_empty && self.quiet.iter().all(|&q| q >= self.threshold)
+    }
+}
+
+/// Runs a set of NICs round-robin. Each turn a NIC reads its next packet,
+/// or -1 if there is none, and runs until it asks for input again.
+pub struct Network<'a> {
+    nics: Vec<Context>,
+    queues: Vec<VecDeque<(i64, i64)>>,
+    router: &'a mut dyn Router,
+    idle: &'a mut dyn IdleDetector,
+    trace: Option<&'a mut dyn Write>,
+    round: usize,
+}
+
+impl<'a> Network<'a> {
+    /// Boots `size` NICs running `prog`, each given its address as input.
+    pub fn new(
+        prog: &[i64],
+        size: usize,
+        router: &'a mut dyn Router,
+        idle: &'a mut dyn IdleDetector,
+    ) -> Network<'a> {
+        let nics = (0..size)
+            .map(|addr| {
+                let mut nic = Context::new(prog);
+                add_input(&mut nic, addr as i64);
+                nic
+            })
+            .collect();
+
+        Network {
+            nics,
+            queues: vec![VecDeque::new(); size],
+            router,
+            idle,
+            trace: None,
+            round: 0,
+        }
+    }
+
+    /// Logs every packet sent, delivered by the router or dropped.
+    pub fn trace(&mut self, out: &'a mut dyn Write) {
+        self.trace = Some(out);
+    }
+
+    fn log(&mut self, packet: &Packet, note: &str) {
+        if let Some(out) = &mut self.trace {
+            writeln!(
+                out,
+                "{}\t{} -> {}\t{}\t{}{}",
+                self.round, packet.src, packet.dest, packet.x, packet.y, note
+            )
+            .expect("Unable to write trace");
+        }
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        if packet.dest >= 0 && (packet.dest as usize) < self.queues.len() {
+            self.queues[packet.dest as usize].push_back((packet.x, packet.y));
+        } else {
+            self.log(&packet, "\tdropped");
+        }
+    }
+
+    fn send(&mut self, packet: Packet) {
+        self.log(&packet, "");
+        if !self.router.intercept(&packet) {
+            self.deliver(packet);
+        }
+    }
+
+    fn turn(&mut self, addr: usize) -> Result<(), String> {
+        let nic = &mut self.nics[addr];
+        // A halted NIC never reads its queue again, so it counts as quiet.
+        if nic.status == Status::Halted {
+            self.queues[addr].clear();
+            self.idle.record(addr, false, false);
+            return Ok(());
+        }
+
+        let received = match self.queues[addr].pop_front() {
+            Some((x, y)) => {
+                add_input(nic, x);
+                add_input(nic, y);
+                true
+            }
+            None => {
+                add_input(nic, -1);
+                false
+            }
+        };
+        run(nic);
+        if let Some(report) = fault_report(nic) {
+            return Err(format!(
+                "NIC {} faulted in round {}: {}",
+                addr,
+                self.round,
+                report.trim_end()
+            ));
+        }
+
+        let mut packets = Vec::new();
+        while nic.output.len() >= 3 {
+            let dest = nic.output.pop_front().unwrap();
+            let x = nic.output.pop_front().unwrap();
+            let y = nic.output.pop_front().unwrap();
+            packets.push(Packet {
+                src: addr,
+                dest,
+                x,
+                y,
+            });
+        }
+
+        self.idle.record(addr, received, !packets.is_empty());
+        for packet in packets {
+            self.send(packet);
+        }
+
+        Ok(())
+    }
+
+    /// Runs rounds until the router has an answer. Fails if a NIC faults or
+    /// the network can no longer make progress: every NIC has halted, or it
+    /// has gone idle and the router has nothing to send.
+    pub fn run(&mut self) -> Result<i64, String> {
+        loop {
+            for addr in 0..self.nics.len() {
+                self.turn(addr)?;
+                if let Some(answer) = self.router.answer() {
+                    return Ok(answer);
+                }
+            }
+
+            if self.nics.iter().all(|nic| nic.status == Status::Halted) {
+                return Err(format!("every NIC halted by round {}", self.round));
+            }
+
+            let queues_empty = self.queues.iter().all(|q| q.is_empty());
+            if self.idle.is_idle(queues_empty) {
+                let packets = self.router.on_idle();
+                if packets.is_empty() && self.router.answer().is_none() {
+                    return Err(format!(
+                        "went idle in round {} with nothing to send",
+                        self.round
+                    ));
+                }
+                for packet in packets {
+                    self.log(&packet, "\tidle");
+                    self.deliver(packet);
+                }
+                if let Some(answer) = self.router.answer() {
+                    return Ok(answer);
+                }
+            }
+
+            self.round += 1;
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Drawing what the Intcode programs output, shared by the days that draw.
+
+pub mod ocr;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// A picture drawn by a program's output, one value per cell. Cells that
+/// were never drawn read as `None`. Rows run top to bottom with `y`
+/// increasing downwards.
+#[derive(Clone, Default)]
+pub struct Screen {
+    cells: HashMap<(i64, i64), i64>,
+    bounds: Option<(i64, i64, i64, i64)>,
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen::default()
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, val: i64) {
+        self.cells.insert((x, y), val);
+        self.include(x, y);
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> Option<i64> {
+        self.cells.get(&(x, y)).cloned()
+    }
+
+    /// Number of cells drawn at least once.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Number of cells currently holding `val`.
+    pub fn count(&self, val: i64) -> usize {
+        self.cells.values().filter(|&&v| v == val).count()
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = ((i64, i64), i64)> + '_ {
+        self.cells.iter().map(|(&pos, &val)| (pos, val))
+    }
+
+    fn include(&mut self, x: i64, y: i64) {
+        self.bounds = Some(match self.bounds {
+            Some((left, top, right, bottom)) => {
+                (left.min(x), top.min(y), right.max(x), bottom.max(y))
+            }
+            None => (x, y, x, y),
+        });
+    }
+
+    /// `(left, top, right, bottom)`, inclusive, of everything drawn.
+    pub fn bounds(&self) -> Option<(i64, i64, i64, i64)> {
+        self.bounds
+    }
+
+    /// Grows the bounds by `margin` cells on every side, leaving a blank
+    /// border when rendering.
+    pub fn expand(&mut self, margin: i64) {
+        if let Some((left, top, right, bottom)) = self.bounds {
+            self.bounds = Some((left - margin, top - margin, right + margin, bottom + margin));
+        }
+    }
+
+    /// Grows the bounds to cover `bounds` too, so that frames of a picture
+    /// drawn over time all have the same size.
+    pub fn extend_to(&mut self, bounds: (i64, i64, i64, i64)) {
+        let (left, top, right, bottom) = bounds;
+        self.include(left, top);
+        self.include(right, bottom);
+    }
+
+    pub fn width(&self) -> usize {
+        self.bounds.map_or(0, |(left, _, right, _)| (right - left + 1) as usize)
+    }
+
+    pub fn height(&self) -> usize {
+        self.bounds.map_or(0, |(_, top, _, bottom)| (bottom - top + 1) as usize)
+    }
+
+    /// One line per row, with `palette` choosing the character for a cell.
+    pub fn render_text(&self, palette: impl Fn(Option<i64>) -> char) -> String {
+        let mut res = String::new();
+
+        if let Some((left, top, right, bottom)) = self.bounds {
+            for y in top..=bottom {
+                for x in left..=right {
+                    res.push(palette(self.get(x, y)));
+                }
+                res.push('\n');
+            }
+        }
+
+        res
+    }
+
+    /// Redraws the screen over the previous frame on an ANSI terminal.
+    pub fn render_terminal(&self, palette: impl Fn(Option<i64>) -> char) {
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[H{}\x1b[J", self.render_text(palette)).unwrap();
+        stdout.flush().unwrap();
+    }
+
+    /// Writes a binary PPM image, each cell `scale` pixels square, with
+    /// `color` choosing the RGB value for a cell.
+    pub fn write_ppm(
+        &self,
+        path: &str,
+        scale: usize,
+        color: impl Fn(Option<i64>) -> [u8; 3],
+    ) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "P6\n{} {}\n255\n", self.width() * scale, self.height() * scale)?;
+
+        if let Some((left, top, right, bottom)) = self.bounds {
+            for y in top..=bottom {
+                let row: Vec<u8> = (left..=right)
+                    .flat_map(|x| color(self.get(x, y)).repeat(scale))
+                    .collect();
+                for _ in 0..scale {
+                    out.write_all(&row)?;
+                }
+            }
+        }
+
+        out.flush()
+    }
+}
+
+/// Decodes `x, y, tile` triplets, drawing each tile on a screen.
+#[derive(Clone, Default)]
+pub struct TileDecoder {
+    pending: Vec<i64>,
+}
+
+impl TileDecoder {
+    /// Takes one output value. Triplets with a negative coordinate aren't
+    /// tiles, such as the score in the arcade cabinet; they are returned
+    /// instead of drawn.
+    pub fn feed(&mut self, screen: &mut Screen, val: i64) -> Option<(i64, i64, i64)> {
+        self.pending.push(val);
+        if self.pending.len() < 3 {
+            return None;
+        }
+
+        let (x, y, tile) = (self.pending[0], self.pending[1], self.pending[2]);
+        self.pending.clear();
+        if x < 0 || y < 0 {
+            return Some((x, y, tile));
+        }
+
+        screen.set(x, y, tile);
+        None
+    }
+}
+
+/// Decodes a stream of ASCII characters, drawing them left to right and
+/// moving down a row on each newline.
+#[derive(Clone, Default)]
+pub struct AsciiDecoder {
+    x: i64,
+    y: i64,
+}
+
+impl AsciiDecoder {
+    /// Takes one output value. Values outside the ASCII range aren't
+    /// drawn; they are returned instead.
+    pub fn feed(&mut self, screen: &mut Screen, val: i64) -> Option<i64> {
+        match val {
+            10 => {
+                self.x = 0;
+                self.y += 1;
+            }
+            0..=127 => {
+                screen.set(self.x, self.y, val);
+                self.x += 1;
+            }
+            _ => return Some(val),
+        }
+
+        None
+    }
+}
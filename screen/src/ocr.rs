@@ -0,0 +1,170 @@
+use std::fmt;
+
+/// The capital letters the puzzles draw, in the small font: 4 pixels wide
+/// (Y is 5) and 6 high, one letter every 5 columns.
+const SMALL: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// The large font: 6 pixels wide and 10 high, one letter every 8 columns.
+#[rustfmt::skip]
+const LARGE: &[(char, [&str; 10])] = &[
+    ('A', ["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', ["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', [".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', [".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', ["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', ["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+    ('K', ["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', ["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', ["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', ["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', ["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', ["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', ["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
+#[derive(Debug)]
+pub enum OcrError {
+    /// Nothing is lit.
+    Blank,
+    /// The lit rows don't match the height of either font.
+    UnknownHeight(usize),
+    /// The letter at `pos` doesn't match any in the font; `nearest` is the
+    /// closest one and how many pixels differ from it.
+    UnknownGlyph {
+        pos: usize,
+        nearest: char,
+        mismatches: usize,
+    },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::Blank => write!(f, "nothing to read"),
+            OcrError::UnknownHeight(height) => {
+                write!(f, "letters are {} pixels high, expected 6 or 10", height)
+            }
+            OcrError::UnknownGlyph {
+                pos,
+                nearest,
+                mismatches,
+            } => write!(
+                f,
+                "unknown glyph at letter {} (closest is {}, {} pixels off)",
+                pos, nearest, mismatches
+            ),
+        }
+    }
+}
+
+/// How many pixels of the cell at column `left` differ from `glyph`. The
+/// cell is `pitch` columns wide, so a glyph must also leave the gap to the
+/// next letter blank.
+fn mismatches(lit: &[Vec<bool>], left: i64, pitch: usize, glyph: &[&str]) -> usize {
+    let mut res = 0;
+
+    for (row, pattern) in lit.iter().zip(glyph) {
+        let pattern = pattern.as_bytes();
+        for off in 0..pitch {
+            let col = left + off as i64;
+            let pixel = col >= 0 && row.get(col as usize).cloned().unwrap_or(false);
+            if pixel != (pattern.get(off) == Some(&b'#')) {
+                res += 1;
+            }
+        }
+    }
+
+    res
+}
+
+/// Reads the cells starting at column `start` until nothing lit is left.
+fn read_from(
+    lit: &[Vec<bool>],
+    start: i64,
+    pitch: usize,
+    font: &[(char, &[&str])],
+) -> Result<String, OcrError> {
+    let mut res = String::new();
+
+    let mut left = start;
+    while lit
+        .iter()
+        .any(|row| row.iter().skip(left.max(0) as usize).any(|&p| p))
+    {
+        let (nearest, diff) = font
+            .iter()
+            .map(|&(c, glyph)| (c, mismatches(lit, left, pitch, glyph)))
+            .min_by_key(|&(_, diff)| diff)
+            .unwrap();
+        if diff != 0 {
+            return Err(OcrError::UnknownGlyph {
+                pos: res.len(),
+                nearest,
+                mismatches: diff,
+            });
+        }
+
+        res.push(nearest);
+        left += pitch as i64;
+    }
+
+    Ok(res)
+}
+
+/// Reads the letters in a picture given as rows of lit pixels. Blank rows
+/// above and below the letters are ignored; the font is chosen by the
+/// height of the rest.
+pub fn read(pixels: &[Vec<bool>]) -> Result<String, OcrError> {
+    let is_lit = |row: &Vec<bool>| row.iter().any(|&p| p);
+    let top = pixels.iter().position(is_lit).ok_or(OcrError::Blank)?;
+    let bottom = pixels.iter().rposition(is_lit).unwrap();
+    let lit = &pixels[top..=bottom];
+
+    let small: Vec<(char, &[&str])> = SMALL.iter().map(|(c, g)| (*c, &g[..])).collect();
+    let large: Vec<(char, &[&str])> = LARGE.iter().map(|(c, g)| (*c, &g[..])).collect();
+    let (pitch, font) = match lit.len() {
+        6 => (5, small),
+        10 => (8, large),
+        height => return Err(OcrError::UnknownHeight(height)),
+    };
+
+    // Letters such as J start with blank columns, so the first lit column
+    // may be a little way into the first cell.
+    let first = lit
+        .iter()
+        .filter_map(|row| row.iter().position(|&p| p))
+        .min()
+        .unwrap() as i64;
+    let mut first_err = None;
+    for shift in 0..pitch as i64 {
+        match read_from(lit, first - shift, pitch, &font) {
+            Ok(text) => return Ok(text),
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+
+    Err(first_err.unwrap())
+}